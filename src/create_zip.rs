@@ -45,6 +45,11 @@ pub fn create_epub_zip_file (out_name: &String, out_dir_path: &Path) {
     zip_remaining_command.arg("content/");
     zip_remaining_command.arg("content/*");
     zip_remaining_command.arg("content/**/*");
+    // Only exists when the theme has fonts
+    if fs::exists("fonts").expect("Error checking fonts existence") {
+        zip_remaining_command.arg("fonts/");
+        zip_remaining_command.arg("fonts/*");
+    }
     zip_remaining_command.output().expect("Zip (remaining) execution");
 
     println!("Done.");
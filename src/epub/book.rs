@@ -0,0 +1,318 @@
+use std::{collections::{BTreeMap, HashSet}, path::Path};
+use crate::{epub::file_templating::split::{Volume, VolumeWork}, html::{process_html::ao3_link_key, process_series::html_to_text, sanitize_html::unescape_html, types::{Anchor, Category, Work, WorkStruct}}};
+
+// Metadata that ends up in content.opf for one ePub
+pub struct BookMetadata {
+    pub title: String,
+    // Used as the dc:identifier of the ePub, so it should be unique per book
+    pub identifier: String,
+    pub creators: Vec<Anchor>,
+    // Calibre series name and series index
+    pub series: Option<(String, usize)>,
+    // dc:description (plain text), and the dates of the dc:date publication and modification events
+    pub description: Option<String>,
+    pub published: Option<String>,
+    pub modified: Option<String>,
+}
+
+// One ePub to be written
+// `name` is both the name of the staging directory and the file name of the ePub (without .epub)
+pub struct Book {
+    pub name: String,
+    pub metadata: BookMetadata,
+    pub works: Vec<Work>,
+    // Only set when this book is one volume of a bigger book that was split up
+    pub volumes: Option<BookVolumes>,
+}
+
+// Which volume a book is, along with every volume of the book it was split from
+// (Used to write the master list of which volume holds each work)
+pub struct BookVolumes {
+    pub current_volume: usize,
+    pub volumes: Vec<Volume>,
+}
+
+// Thresholds past which a book gets split into volumes
+// Any limit left as `None` is ignored
+#[derive(Default)]
+pub struct VolumeLimits {
+    pub max_bytes: Option<usize>,
+    pub max_words: Option<usize>,
+    pub max_works: Option<usize>,
+}
+
+impl VolumeLimits {
+    pub fn is_unlimited (&self) -> bool {
+        self.max_bytes.is_none() && self.max_words.is_none() && self.max_works.is_none()
+    }
+
+    fn exceeded_by (&self, bytes: usize, words: usize, works: usize) -> bool {
+        self.max_bytes.is_some_and(| max | bytes > max)
+            || self.max_words.is_some_and(| max | words > max)
+            || self.max_works.is_some_and(| max | works > max)
+    }
+}
+
+// Rough estimate of how many bytes a work takes up in the ePub (before compression)
+// Chapter text makes up nearly all of it, the rest is a flat allowance for each generated page
+fn estimate_bytes (work: &Work) -> usize {
+    const PAGE_OVERHEAD: usize = 2048;
+    work.work_structs().iter().map(| work_struct | {
+        let chapter_bytes: usize = work_struct.chapters.iter()
+            .map(| chapter | chapter.data.len() + chapter.summary.len() + PAGE_OVERHEAD)
+            .sum();
+        chapter_bytes + work_struct.summary.len() + 2 * PAGE_OVERHEAD
+    }).sum()
+}
+
+impl Book {
+
+    // The default: every work in one big ePub
+    pub fn anthology (out_name: &str, works: Vec<Work>) -> Self {
+        Book {
+            name: String::from(out_name),
+            metadata: BookMetadata {
+                title: String::from(out_name),
+                identifier: String::from(out_name),
+                creators: Vec::new(),
+                series: None,
+                description: None,
+                published: None,
+                modified: None,
+            },
+            works,
+            volumes: None,
+        }
+    }
+
+    // One ePub for every `Work::Single` and every `Work::Series`
+    pub fn per_work (out_name: &str, works: Vec<Work>) -> Vec<Self> {
+        let mut used_names: HashSet<String> = HashSet::new();
+        works.into_iter().map(| work | {
+            let metadata = match &work {
+                Work::Single(work_struct) => BookMetadata {
+                    title: work_struct.title.clone(),
+                    identifier: work_struct.link.clone(),
+                    creators: vec![ work_struct.author.clone() ],
+                    // A single work can still be part of a series if it is the only part that was downloaded
+                    series: work_struct.series.first().map(| series | (series.name.clone(), series.part_number)),
+                    description: None,
+                    published: None,
+                    modified: None,
+                },
+                Work::Series(work_series, work_structs) => {
+                    // Every author that contributed to the series, in order of first appearance
+                    // The series page (if it was saved) lists the creators of the whole series, not just of the
+                    //      works that were downloaded
+                    let mut creators: Vec<Anchor> = work_series.details.as_ref().map_or(Vec::new(), | details | details.creators.clone());
+                    for work_struct in work_structs {
+                        if !creators.iter().any(| creator | ao3_link_key(&creator.link) == ao3_link_key(&work_struct.author.link)) {
+                            creators.push(work_struct.author.clone());
+                        }
+                    }
+
+                    // Works in a series are sorted by part number, so the first work is the earliest part in this book
+                    let first_part = work_structs.first()
+                        .map(| work_struct | work_struct.part_number_in(&work_series.link))
+                        .unwrap_or(1);
+
+                    BookMetadata {
+                        title: work_series.title.clone(),
+                        identifier: work_series.link.clone(),
+                        creators,
+                        series: Some((work_series.title.clone(), first_part)),
+                        description: work_series.details.as_ref()
+                            .map(| details | html_to_text(&details.summary))
+                            .filter(| description | !description.is_empty()),
+                        published: work_series.details.as_ref().and_then(| details | details.begun.clone()),
+                        modified: work_series.details.as_ref().and_then(| details | details.updated.clone()),
+                    }
+                },
+            };
+
+            // Two works can share a title, but they can't share a staging directory
+            let base_name = format!("{out_name} - {}", file_name_safe(&metadata.title));
+            let mut name = base_name.clone();
+            let mut duplicate = 1;
+            while !used_names.insert(name.clone()) {
+                duplicate += 1;
+                name = format!("{base_name} ({duplicate})");
+            }
+
+            Book {
+                name,
+                metadata,
+                works: vec![ work ],
+                volumes: None,
+            }
+        }).collect()
+    }
+
+    // One ePub for every subcategory of `category` (for example, one ePub per fandom)
+    // A work with more than one subcategory (a crossover) goes into every matching ePub, unless `crossovers` is set,
+    //      in which case all of those works go into a single "Crossovers" ePub instead
+    // Works with no subcategory at all go into an "Untagged" ePub
+    // Each part of a series is placed on its own, so a series whose parts don't all match ends up split into one
+    //      (partial) series per ePub, each keeping the series' header
+    pub fn partition (out_name: &str, category: &Category, crossovers: bool, works: Vec<Work>) -> Vec<Self> {
+        // Names of the ePubs a single work goes into
+        let partition_names = | work_struct: &WorkStruct | -> Vec<String> {
+            let mut names: Vec<String> = Vec::new();
+            for entry in work_struct.category_entries(category) {
+                if !names.contains(&entry.name) {
+                    names.push(entry.name);
+                }
+            }
+
+            match names.len() {
+                0 => vec![ String::from("Untagged") ],
+                1 => names,
+                _ if crossovers => vec![ String::from("Crossovers") ],
+                _ => names,
+            }
+        };
+
+        // Keyed by subcategory name so that the resulting ePubs come out alphabetically
+        let mut partitions: BTreeMap<String, Vec<Work>> = BTreeMap::new();
+
+        for work in works {
+            match work {
+                Work::Single(work_struct) => {
+                    for name in partition_names(&work_struct) {
+                        partitions.entry(name).or_default().push(Work::Single(work_struct.clone()));
+                    }
+                },
+                Work::Series(work_series, work_structs) => {
+                    // Parts of the series for each ePub, kept in part order
+                    let mut series_parts: BTreeMap<String, Vec<WorkStruct>> = BTreeMap::new();
+                    for work_struct in work_structs {
+                        for name in partition_names(&work_struct) {
+                            series_parts.entry(name).or_default().push(work_struct.clone());
+                        }
+                    }
+
+                    for (name, parts) in series_parts {
+                        partitions.entry(name).or_default().push(Work::Series(work_series.clone(), parts));
+                    }
+                },
+            }
+        }
+
+        partitions.into_iter().map(| (name, works) | {
+            let title = format!("{out_name} - {name}");
+            Book {
+                name: format!("{out_name} - {}", file_name_safe(&name)),
+                metadata: BookMetadata {
+                    identifier: format!("{out_name}-{category}-{name}"),
+                    title,
+                    creators: Vec::new(),
+                    series: None,
+                    description: None,
+                    published: None,
+                    modified: None,
+                },
+                works,
+                volumes: None,
+            }
+        }).collect()
+    }
+
+    // Split this book into "<title> Vol. 1..N" whenever it goes past any of `limits`
+    // Works are kept in order and a series is never split across volumes, so a single work or series that is
+    //      bigger than the limits on its own gets a volume all to itself
+    // Books that fit within the limits are returned as they are
+    pub fn split_into_volumes (self, limits: &VolumeLimits) -> Vec<Self> {
+        if limits.is_unlimited() {
+            return vec![ self ];
+        }
+
+        let mut volume_works: Vec<Vec<Work>> = vec![ Vec::new() ];
+        let (mut bytes, mut words, mut work_count) = (0, 0, 0);
+        for work in self.works {
+            let work_bytes = estimate_bytes(&work);
+            let work_words: usize = work.work_structs().iter().map(| work_struct | work_struct.word_count()).sum();
+            let work_work_count = work.work_structs().len();
+
+            let current = volume_works.last_mut().unwrap();
+            if !current.is_empty() && limits.exceeded_by(bytes + work_bytes, words + work_words, work_count + work_work_count) {
+                volume_works.push(Vec::new());
+                (bytes, words, work_count) = (0, 0, 0);
+            }
+
+            bytes += work_bytes;
+            words += work_words;
+            work_count += work_work_count;
+            volume_works.last_mut().unwrap().push(work);
+        }
+
+        if volume_works.len() == 1 {
+            return vec![ Book { works: volume_works.remove(0), ..self } ];
+        }
+
+        // Listing of every volume and the works inside of it, shared between all the volumes
+        let volumes: Vec<Volume> = volume_works.iter().enumerate().map(| (index, works) | {
+            let mut listed_works: Vec<VolumeWork> = Vec::new();
+            for work in works {
+                let series_title = match work {
+                    Work::Series(work_series, _) => Some(work_series.title.clone()),
+                    Work::Single(_) => None,
+                };
+                for work_struct in work.work_structs() {
+                    listed_works.push(VolumeWork { 
+                        id: work_struct.id, 
+                        title: work_struct.title.clone(), 
+                        series_title: series_title.clone(),
+                    });
+                }
+            }
+
+            Volume {
+                number: index + 1,
+                title: format!("{} Vol. {}", self.metadata.title, index + 1),
+                works: listed_works,
+            }
+        }).collect();
+
+        volume_works.into_iter().enumerate().map(| (index, works) | {
+            let number = index + 1;
+            Book {
+                name: format!("{} Vol. {number}", self.name),
+                metadata: BookMetadata {
+                    title: format!("{} Vol. {number}", self.metadata.title),
+                    identifier: format!("{}-vol-{number}", self.metadata.identifier),
+                    creators: self.metadata.creators.clone(),
+                    // Volumes make up a series of their own, unless the book was already part of a series
+                    series: self.metadata.series.clone().or(Some((self.metadata.title.clone(), number))),
+                    description: self.metadata.description.clone(),
+                    published: self.metadata.published.clone(),
+                    modified: self.metadata.modified.clone(),
+                },
+                works,
+                volumes: Some(BookVolumes {
+                    current_volume: number,
+                    volumes: volumes.clone(),
+                }),
+            }
+        }).collect()
+    }
+}
+
+// Titles come straight out of AO3's HTML, so they can contain escaped entities and characters that
+//      aren't allowed in file names
+// Strip those out so the title can be used as a staging directory / ePub file name
+pub fn file_name_safe (title: &str) -> String {
+    let unescaped = unescape_html(title);
+
+    let safe: String = unescaped.chars().map(| ch | match ch {
+        '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+        ch if ch.is_control() => '_',
+        ch => ch,
+    }).collect();
+
+    // Path::file_name() rejects names like ".." so fall back to something generic
+    let safe = String::from(safe.trim().trim_matches('.'));
+    match Path::new(&safe).file_name() {
+        Some(_) => safe,
+        None => String::from("untitled"),
+    }
+}
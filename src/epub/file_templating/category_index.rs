@@ -1,16 +1,211 @@
+use std::collections::HashMap;
+
 use askama::Template;
-use crate::html::types::*;
+use crate::html::{process_html::ao3_link_key, titles::sort_key, types::*};
+use serde::Serialize;
+use crate::epub::{file_templating::pagination::{Pagination, page_file_name, paginate}, locale::Locale, template_overrides::OverridableTemplate};
 
+#[derive(Serialize)]
 pub struct CategoryListing <'a> {
     pub id: usize,
+    pub link: String,
     pub name: String,
     pub count: usize,
     pub works: Vec<&'a WorkStruct>,
 }
 
-#[derive(Template)]
+impl <'a> CategoryListing <'a> {
+
+    // Accumulate listing of subcategories for one category
+    // Iterate over all the works and get all subcategories inside of this category
+    //      and store them as a CategoryListing
+    // When more than one work shares a category/sub-category, then add that work to the list
+    //      of works in that CategoryListing
+    // Key is the link to the subcategory (subcategories are always of `Anchor` struct type, so they all have a link and a name),
+    //      through `ao3_link_key` so that downloads and saved pages end up in the same listing
+    // Value is the accumulated list of all works under the category/subcategory combination
+    pub fn accumulate (category: &Category, work_structs: &[&'a WorkStruct]) -> HashMap<String, Self> {
+        let mut listings: HashMap<String, CategoryListing> = HashMap::new();
+
+        for work in work_structs {
+
+            // Check every subcategory in this work/category combination
+            for work_category_entry in work.category_entries(category) {
+                // If this subcategory was found already, add the work the accumulating list
+                if let Some(existing_listing) = listings.get_mut(ao3_link_key(&work_category_entry.link)) {
+                    existing_listing.count += 1;
+                    existing_listing.works.push(work);
+                }
+                // Otherwise create a new CategoryListing object for the subcategory
+                else {
+                    listings.insert(String::from(ao3_link_key(&work_category_entry.link)), CategoryListing {
+                        id: listings.len(),
+                        link: work_category_entry.link,
+                        name: work_category_entry.name,
+                        count: 1,
+                        works: vec![ work ]
+                    });
+                }
+            }
+        }
+
+        listings
+    }
+}
+
+// How the entries of a category index are ordered, set with --index_order
+// Titles are always alphabetical
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexOrder {
+    // Most works first
+    Count,
+    // A to Z, in lettered sections with a jump bar at the top of each page
+    Alphabetical,
+}
+
+impl std::str::FromStr for IndexOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.trim().to_lowercase()[..] {
+            "count" => Ok(IndexOrder::Count),
+            "alphabetical" | "alpha" => Ok(IndexOrder::Alphabetical),
+            other => Err(format!("Unknown index order '{other}'.  Expected one of: count, alphabetical")),
+        }
+    }
+}
+
+// Run of entries that start with the same letter
+// Indexes that aren't alphabetical have a single section without a letter
+#[derive(Serialize)]
+pub struct IndexSection <'a> {
+    pub letter: Option<String>,
+    // Element id of the section heading, for the jump bar to link to
+    pub id: String,
+    pub entries: Vec<&'a CategoryListing<'a>>,
+}
+
+// One letter of the jump bar
+// `link` is None for letters that have no entries
+#[derive(Clone, Serialize)]
+pub struct JumpLetter {
+    pub letter: String,
+    pub link: Option<String>,
+}
+
+#[derive(Template, Serialize)]
 #[template(path = "category-index.html")]
 pub struct CategoryIndex <'a> {
+    pub locale: &'a Locale,
     pub category: String,
-    pub categories: Vec<&'a CategoryListing<'a>>
-}
\ No newline at end of file
+    pub subcategory: Option<String>,
+    pub subindexes: Vec<Anchor>,
+    pub jump_letters: Vec<JumpLetter>,
+    pub sections: Vec<IndexSection<'a>>,
+    pub pagination: Pagination,
+}
+
+// Name an entry is sorted and lettered by
+fn entry_name <'a> (category: &Category, listing: &'a CategoryListing) -> &'a String {
+    match category {
+        Category::Titles => &listing.works[0].title,
+        _ => &listing.name,
+    }
+}
+
+// Letter an entry gets filed under ("#" for anything that doesn't start with a letter)
+fn entry_letter (category: &Category, listing: &CategoryListing) -> String {
+    match sort_key(entry_name(category, listing)).chars().next() {
+        Some(first) if first.is_alphabetic() => first.to_uppercase().collect(),
+        _ => String::from("#"),
+    }
+}
+
+fn section_id (letter: &str) -> String {
+    format!("letter-{}", letter.chars().next().map_or(0, | ch | ch as u32))
+}
+
+impl <'a> CategoryIndex <'a> {
+
+    // Every page of a category index, along with the file name of each page
+    // `file_stem` is the file name of the first page without the extension (like "index")
+    #[allow(clippy::too_many_arguments)]
+    pub fn pages (locale: &'a Locale, category: &Category, subcategory: Option<String>, subindexes: Vec<Anchor>, mut listings: Vec<&'a CategoryListing<'a>>, order: IndexOrder, page_size: Option<usize>, file_stem: &str) -> Vec<(String, Self)> {
+        let alphabetical = order == IndexOrder::Alphabetical || *category == Category::Titles;
+        if alphabetical {
+            listings.sort_by_cached_key(| listing | (sort_key(entry_name(category, listing)), listing.id));
+        }
+        else {
+            listings.sort_by_cached_key(| listing | (std::cmp::Reverse(listing.count), sort_key(&listing.name), listing.id));
+        }
+
+        let pages = paginate(&listings, page_size);
+        let total_pages = pages.len();
+
+        // Jump bar -> "#", then every letter from A to Z, then any other letters (accented, other alphabets, ...)
+        //      that entries start with, each linking to the page where that letter starts
+        let mut jump_letters: Vec<JumpLetter> = Vec::new();
+        if alphabetical {
+            let mut first_page_of_letter: Vec<(String, usize)> = Vec::new();
+            for (page_index, page) in pages.iter().enumerate() {
+                for listing in page {
+                    let letter = entry_letter(category, listing);
+                    if !first_page_of_letter.iter().any(| (existing, _) | *existing == letter) {
+                        first_page_of_letter.push((letter, page_index));
+                    }
+                }
+            }
+
+            let mut letters: Vec<String> = vec![ String::from("#") ];
+            letters.extend(('A'..='Z').map(String::from));
+            let mut other_letters: Vec<String> = first_page_of_letter.iter()
+                .map(| (letter, _) | letter.clone())
+                .filter(| letter | !letters.contains(letter))
+                .collect();
+            other_letters.sort();
+            letters.extend(other_letters);
+
+            jump_letters = letters.into_iter().filter_map(| letter | {
+                let page_index = first_page_of_letter.iter().find(| (existing, _) | *existing == letter).map(| (_, page_index) | *page_index);
+                // "#" only shows up when something is filed under it
+                if letter == "#" && page_index.is_none() {
+                    return None;
+                }
+                Some(JumpLetter {
+                    link: page_index.map(| page_index | format!("{}#{}", page_file_name(file_stem, page_index), section_id(&letter))),
+                    letter,
+                })
+            }).collect();
+        }
+
+        pages.into_iter().enumerate().map(| (page_index, page) | {
+            let mut sections: Vec<IndexSection> = Vec::new();
+            for listing in page {
+                let letter = alphabetical.then(|| entry_letter(category, listing));
+                match sections.last_mut() {
+                    Some(section) if section.letter == letter => section.entries.push(listing),
+                    _ => sections.push(IndexSection {
+                        id: letter.as_deref().map_or(String::from("entries"), section_id),
+                        letter,
+                        entries: vec![ listing ],
+                    }),
+                }
+            }
+
+            (page_file_name(file_stem, page_index), CategoryIndex {
+                locale,
+                category: category.to_string(),
+                subcategory: subcategory.clone(),
+                // Sub-indexes are only listed on the first page
+                subindexes: if page_index == 0 { subindexes.clone() } else { Vec::new() },
+                jump_letters: jump_letters.clone(),
+                sections,
+                pagination: Pagination::new(file_stem, page_index, total_pages),
+            })
+        }).collect()
+    }
+}
+
+impl OverridableTemplate for CategoryIndex<'_> {
+    const TEMPLATE_PATH: &'static str = "category-index.html";
+}
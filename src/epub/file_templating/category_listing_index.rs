@@ -1,10 +1,81 @@
+use std::collections::HashMap;
+
 use askama::Template;
-use crate::html::types::*;
+use crate::html::{process_html::ao3_link_key, types::*};
+use serde::Serialize;
+use crate::epub::{file_templating::{category_index::CategoryListing, pagination::Pagination}, locale::Locale, template_overrides::OverridableTemplate};
+
+// Categories shown in the "frequently appears with" section of listing pages, and how many entries of each
+const CO_OCCURRENCE_CATEGORIES: [Category; 3] = [ Category::Relationships, Category::Characters, Category::Tags ];
+const CO_OCCURRENCE_COUNT: usize = 10;
+
+// Another listing that shares works with the listing of the page
+// `count` is how many works the two listings share
+#[derive(Serialize)]
+pub struct CoOccurrence {
+    pub link: String,
+    pub name: String,
+    pub count: usize,
+}
 
-#[derive(Template)]
+// Every co-occurring listing of one category, most shared works first
+#[derive(Serialize)]
+pub struct CoOccurrenceGroup {
+    pub category: String,
+    pub entries: Vec<CoOccurrence>,
+}
+
+#[derive(Template, Serialize)]
 #[template(path = "category-listing-index.html")]
 pub struct CategoryListingIndex <'a> {
+    pub locale: &'a Locale,
     pub category: String,
     pub listing_name: &'a String,
-    pub listing: &'a Vec<&'a WorkStruct>
-}
\ No newline at end of file
+    // Works of this listing on this page
+    pub listing: Vec<&'a WorkStruct>,
+    // Members and co-occurrences are only filled in on the first page
+    pub members: Vec<Anchor>,
+    // "Frequently appears with" -> Relationships, characters and tags that show up the most in the works of this listing
+    pub co_occurrences: Vec<CoOccurrenceGroup>,
+    pub pagination: Pagination,
+}
+
+// The relationships, characters and tags that show up the most in the works of `listing` (other than `listing` itself)
+// Links point at the listing pages of each of them
+pub fn co_occurrences (listing: &CategoryListing, category_listings: &HashMap<Category, HashMap<String, CategoryListing>>) -> Vec<CoOccurrenceGroup> {
+    CO_OCCURRENCE_CATEGORIES.iter().filter_map(| category | {
+        // Category wasn't indexed, so there are no listing pages to link to
+        let other_listings = category_listings.get(category)?;
+
+        let mut counts: HashMap<&String, usize> = HashMap::new();
+        for work in &listing.works {
+            for entry in work.category_entries(category) {
+                if let Some((link, _)) = other_listings.get_key_value(ao3_link_key(&entry.link)) && link != ao3_link_key(&listing.link) {
+                    *counts.entry(link).or_default() += 1;
+                }
+            }
+        }
+
+        let mut entries: Vec<CoOccurrence> = counts.into_iter().map(| (link, count) | {
+            let other_listing = other_listings.get(link).unwrap();
+            CoOccurrence {
+                link: format!("../../indexes/{category}/{category}-{}-listing.xhtml", other_listing.id),
+                name: other_listing.name.clone(),
+                count,
+            }
+        }).collect();
+
+        // Ties are broken by name so the order is the same every time the ePub is built
+        entries.sort_by(| a, b | b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+        entries.truncate(CO_OCCURRENCE_COUNT);
+
+        match entries.is_empty() {
+            true => None,
+            false => Some(CoOccurrenceGroup { category: category.to_string(), entries }),
+        }
+    }).collect()
+}
+
+impl OverridableTemplate for CategoryListingIndex<'_> {
+    const TEMPLATE_PATH: &'static str = "category-listing-index.html";
+}
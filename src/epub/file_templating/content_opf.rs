@@ -1,14 +1,39 @@
 use askama::Template;
+use crate::{epub::{book::BookMetadata, locale::Locale}, html::{titles::title_sort, types::Anchor}};
+use serde::Serialize;
+use crate::epub::template_overrides::OverridableTemplate;
 
-#[derive(Template)]
+// Anything in the ePub other than xhtmls that needs to be listed in the manifest
+#[derive(Clone, Serialize)]
+pub struct ManifestItem {
+    pub id: String,
+    pub href: String,
+    pub media_type: String,
+}
+
+#[derive(Template, Serialize)]
 #[template(path = "content_opf.html")]
 pub struct ContentOpf {
-    pub output_name: String,
-    pub xhtmls: Vec<String>
+    pub title: String,
+    // calibre:title_sort, the title with any leading article moved to the end
+    pub title_sort: String,
+    // BCP 47 code for dc:language
+    pub language: String,
+    // Title of the cover page in the guide
+    pub cover_title: String,
+    pub identifier: String,
+    pub creators: Vec<Anchor>,
+    pub series: Option<(String, usize)>,
+    pub description: Option<String>,
+    pub published: Option<String>,
+    pub modified: Option<String>,
+    pub xhtmls: Vec<String>,
+    pub resources: Vec<ManifestItem>,
+    pub cover_page: Option<String>,
 }
 
 impl ContentOpf {
-    pub fn new(output_name: String, xhtmls: &Vec<String>) -> Self {
+    pub fn new(output_name: String, metadata: &BookMetadata, language: &str, locale: &Locale, xhtmls: &Vec<String>, resources: &[ManifestItem], cover_page: Option<&String>) -> Self {
 
         // Remove "output_name/" from the beginning of each xhtml path
         // These paths are created relative to the working directory, but when we add them
@@ -21,8 +46,23 @@ impl ContentOpf {
         }).collect();
 
         ContentOpf { 
-            output_name: output_name, 
-            xhtmls: repl
+            title: metadata.title.clone(),
+            title_sort: title_sort(&metadata.title),
+            language: String::from(language),
+            cover_title: String::from(locale.cover),
+            identifier: metadata.identifier.clone(),
+            creators: metadata.creators.clone(),
+            series: metadata.series.clone(),
+            description: metadata.description.clone(),
+            published: metadata.published.clone(),
+            modified: metadata.modified.clone(),
+            cover_page: cover_page.map(| cover_page | cover_page.replace(&xhtml_beginning, "")),
+            xhtmls: repl,
+            resources: resources.to_vec(),
         }
     }
-}
\ No newline at end of file
+}
+
+impl OverridableTemplate for ContentOpf {
+    const TEMPLATE_PATH: &'static str = "content_opf.html";
+}
@@ -0,0 +1,141 @@
+use std::{collections::HashMap, path::Path};
+
+use askama::Template;
+use crate::html::{sanitize_html::unescape_html, types::{Category, Work, WorkStruct}};
+use serde::Serialize;
+use crate::epub::{locale::Locale, template_overrides::OverridableTemplate};
+
+// Generated cover image for books that weren't given a cover by the user
+// 600x800 SVG with the title at the top, some details about the contents in the middle, and a footer at the bottom
+#[derive(Template, Serialize)]
+#[template(path = "cover.svg")]
+pub struct CoverImage {
+    pub background: String,
+    pub title_lines: Vec<String>,
+    pub detail_lines: Vec<String>,
+    pub footer: String,
+}
+
+// Cover page -> First page in the spine, which just shows the cover image
+#[derive(Template, Serialize)]
+#[template(path = "cover.html")]
+pub struct CoverPage <'a> {
+    pub locale: &'a Locale,
+    pub title: String,
+    pub image_href: String,
+}
+
+// Media type of a user-provided cover image, or None if it's not a type ePub readers can show
+pub fn cover_media_type (path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    match &extension[..] {
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "png" => Some("image/png"),
+        "gif" => Some("image/gif"),
+        "svg" => Some("image/svg+xml"),
+        _ => None,
+    }
+}
+
+// Greedily wrap `text` into lines of at most `max_chars` characters (a single word longer than that gets its own line)
+// SVG has no text wrapping of its own
+fn wrap_text (text: &str, max_chars: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > max_chars {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+// Pick a background color (dark enough to read white text on) from the title, so different books get different covers
+fn background_for (title: &str) -> String {
+    const PALETTE: [&str; 8] = [ "#7a1c1c", "#1c3f7a", "#1f5e3a", "#5a2a6e", "#6e4a1c", "#1c5e63", "#4a4a4a", "#802050" ];
+    let hash = title.bytes().fold(5381u32, | hash, byte | hash.wrapping_mul(33) ^ byte as u32);
+    String::from(PALETTE[hash as usize % PALETTE.len()])
+}
+
+// Fandom names of `work_structs` ordered by how many of the works they show up in, descending
+fn top_fandoms (work_structs: &[&WorkStruct], count: usize) -> Vec<String> {
+    let mut fandom_counts: HashMap<String, usize> = HashMap::new();
+    for work_struct in work_structs {
+        for fandom in work_struct.category_entries(&Category::Fandoms) {
+            *fandom_counts.entry(unescape_html(&fandom.name)).or_default() += 1;
+        }
+    }
+
+    let mut fandoms: Vec<(String, usize)> = fandom_counts.into_iter().collect();
+    fandoms.sort_by(| a, b | b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    fandoms.into_iter().take(count).map(| (fandom, _) | fandom).collect()
+}
+
+// Format a number with commas, the same way AO3 does
+pub fn with_commas (number: usize) -> String {
+    let digits = number.to_string();
+    let mut out = String::new();
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(digit);
+    }
+    out
+}
+
+impl CoverImage {
+
+    // A book made up of a single work (or a single series) gets a cover with the title, author and fandoms of that work
+    // Anything bigger gets an anthology cover with the number of works, the top fandoms, and the total word count
+    pub fn new (locale: &Locale, title: &str, works: &[Work]) -> Self {
+        let work_structs: Vec<&WorkStruct> = works.iter().flat_map(| work | work.work_structs()).collect();
+        let total_words: usize = work_structs.iter().map(| work_struct | work_struct.word_count()).sum();
+        let title = unescape_html(title);
+
+        let mut detail_lines: Vec<String> = Vec::new();
+        if works.len() == 1 {
+            let mut authors: Vec<String> = Vec::new();
+            for work_struct in &work_structs {
+                let author = unescape_html(&work_struct.author.name);
+                if !authors.contains(&author) {
+                    authors.push(author);
+                }
+            }
+            detail_lines.extend(wrap_text(&format!("{} {}", locale.by, authors.join(", ")), 30));
+            detail_lines.push(String::new());
+            for fandom in top_fandoms(&work_structs, 3) {
+                detail_lines.extend(wrap_text(&fandom, 34));
+            }
+        }
+        else {
+            detail_lines.push(format!("{} {}", work_structs.len(), locale.works));
+            detail_lines.push(String::new());
+            for fandom in top_fandoms(&work_structs, 5) {
+                detail_lines.extend(wrap_text(&fandom, 34));
+            }
+        }
+
+        CoverImage {
+            background: background_for(&title),
+            title_lines: wrap_text(&title, 20).into_iter().take(5).collect(),
+            detail_lines: detail_lines.into_iter().take(10).collect(),
+            footer: format!("{} {}", with_commas(total_words), locale.words),
+        }
+    }
+}
+
+impl OverridableTemplate for CoverImage {
+    const TEMPLATE_PATH: &'static str = "cover.svg";
+}
+
+impl OverridableTemplate for CoverPage<'_> {
+    const TEMPLATE_PATH: &'static str = "cover.html";
+}
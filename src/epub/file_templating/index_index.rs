@@ -1,9 +1,17 @@
 use askama::Template;
 use crate::html::types::Category;
+use serde::Serialize;
+use crate::epub::{locale::Locale, template_overrides::OverridableTemplate};
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "index_index.html")]
 pub struct IndexIndex <'a> {
+    pub locale: &'a Locale,
     pub output_name: String,
     pub categories: &'a [Category],
-}
\ No newline at end of file
+    pub has_volumes: bool,
+}
+
+impl OverridableTemplate for IndexIndex<'_> {
+    const TEMPLATE_PATH: &'static str = "index_index.html";
+}
@@ -1,8 +1,11 @@
 pub(crate) mod toc;
 pub(crate) mod split;
 pub(crate) mod content_opf;
+pub(crate) mod cover;
 pub(crate) mod category_index;
 pub(crate) mod category_listing_index;
 pub(crate) mod work;
 pub(crate) mod works_index;
-pub(crate) mod index_index;
\ No newline at end of file
+pub(crate) mod index_index;
+pub(crate) mod stats;
+pub(crate) mod pagination;
\ No newline at end of file
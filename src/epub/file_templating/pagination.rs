@@ -0,0 +1,42 @@
+use serde::Serialize;
+
+// Where a page sits in an index that was split over several xhtmls
+// `previous` and `next` are file names of the neighbouring pages (all pages of an index live in the same directory)
+#[derive(Serialize)]
+pub struct Pagination {
+    pub number: usize,
+    pub total: usize,
+    pub previous: Option<String>,
+    pub next: Option<String>,
+}
+
+// The first page keeps the plain file name, so everything that links to the index still works
+//      <stem>.xhtml, <stem>-page-2.xhtml, <stem>-page-3.xhtml, ...
+pub fn page_file_name (stem: &str, page_index: usize) -> String {
+    match page_index {
+        0 => format!("{stem}.xhtml"),
+        _ => format!("{stem}-page-{}.xhtml", page_index + 1),
+    }
+}
+
+// Split `items` into pages of at most `page_size` items (everything on one page when `page_size` is None)
+// Always returns at least one page, even if it's empty, so that every index gets written
+pub fn paginate <T: Clone> (items: &[T], page_size: Option<usize>) -> Vec<Vec<T>> {
+    match page_size {
+        Some(page_size) if page_size > 0 && items.len() > page_size => {
+            items.chunks(page_size).map(| chunk | chunk.to_vec()).collect()
+        },
+        _ => vec![ items.to_vec() ],
+    }
+}
+
+impl Pagination {
+    pub fn new (stem: &str, page_index: usize, total: usize) -> Self {
+        Pagination {
+            number: page_index + 1,
+            total,
+            previous: (page_index > 0).then(|| page_file_name(stem, page_index - 1)),
+            next: (page_index + 1 < total).then(|| page_file_name(stem, page_index + 1)),
+        }
+    }
+}
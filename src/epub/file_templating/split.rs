@@ -0,0 +1,32 @@
+use askama::Template;
+use serde::Serialize;
+use crate::epub::{locale::Locale, template_overrides::OverridableTemplate};
+
+// One work listed on the volumes index
+#[derive(Clone, Serialize)]
+pub struct VolumeWork {
+    pub id: usize,
+    pub title: String,
+    pub series_title: Option<String>,
+}
+
+// One volume of an anthology that was too big for a single ePub
+#[derive(Clone, Serialize)]
+pub struct Volume {
+    pub number: usize,
+    pub title: String,
+    pub works: Vec<VolumeWork>,
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "volumes_index.html")]
+pub struct VolumesIndex <'a> {
+    pub locale: &'a Locale,
+    pub output_name: String,
+    pub current_volume: usize,
+    pub volumes: &'a Vec<Volume>,
+}
+
+impl OverridableTemplate for VolumesIndex<'_> {
+    const TEMPLATE_PATH: &'static str = "volumes_index.html";
+}
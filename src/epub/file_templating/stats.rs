@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+
+use askama::Template;
+use crate::html::types::{Category, WorkStruct};
+use serde::Serialize;
+use crate::epub::{file_templating::{category_index::CategoryListing, cover::with_commas}, locale::Locale, template_overrides::OverridableTemplate};
+
+// Upper bounds (exclusive) of the word count histogram buckets, the last bucket has no upper bound
+const WORD_COUNT_BUCKETS: [usize; 5] = [ 1_000, 5_000, 10_000, 50_000, 100_000 ];
+
+// How many tags / relationships to show in the "most common" lists
+const TOP_COUNT: usize = 10;
+
+// One line of a stats table, linking to the category listing page of that subcategory
+#[derive(Serialize)]
+pub struct StatsRow {
+    pub name: String,
+    pub link: String,
+    pub works: usize,
+    pub words: String,
+}
+
+// One bar of the word count histogram
+// `percent` is the width of the bar, relative to the biggest bucket
+#[derive(Serialize)]
+pub struct HistogramBucket {
+    pub label: String,
+    pub works: usize,
+    pub percent: usize,
+}
+
+// Library stats (indexes/stats.xhtml) ->
+//      Summary of everything in the ePub: totals, word counts by fandom and author, a histogram of work lengths,
+//      the rating breakdown and the most common tags and relationships
+#[derive(Template, Serialize)]
+#[template(path = "stats.html")]
+pub struct LibraryStats <'a> {
+    pub locale: &'a Locale,
+    pub output_name: String,
+    pub total_works: String,
+    pub total_chapters: String,
+    pub total_words: String,
+    pub reading_time: String,
+    pub word_count_histogram: Vec<HistogramBucket>,
+    pub ratings: Vec<StatsRow>,
+    pub fandoms: Vec<StatsRow>,
+    pub authors: Vec<StatsRow>,
+    pub top_tags: Vec<StatsRow>,
+    pub top_relationships: Vec<StatsRow>,
+}
+
+// Rows for every listing of `category`, ordered by total words (`by_words`) or by number of works, descending
+// Categories that weren't indexed (or have no works) just get no rows
+fn stats_rows (category_listings: &HashMap<Category, HashMap<String, CategoryListing>>, category: &Category, by_words: bool) -> Vec<StatsRow> {
+    let Some(listings) = category_listings.get(category) else {
+        return Vec::new();
+    };
+
+    let mut rows: Vec<(usize, StatsRow)> = listings.values().map(| listing | {
+        let words: usize = listing.works.iter().map(| work | work.word_count()).sum();
+        (words, StatsRow {
+            name: listing.name.clone(),
+            link: format!("{category}/{category}-{}-listing.xhtml", listing.id),
+            works: listing.count,
+            words: with_commas(words),
+        })
+    }).collect();
+
+    // Ties are broken by name so the order is the same every time the ePub is built
+    rows.sort_by(| (a_words, a), (b_words, b) | {
+        let by_count = if by_words { b_words.cmp(a_words) } else { b.works.cmp(&a.works) };
+        by_count.then_with(|| a.name.cmp(&b.name))
+    });
+    rows.into_iter().map(| (_, row) | row).collect()
+}
+
+fn word_count_histogram (work_structs: &[&WorkStruct]) -> Vec<HistogramBucket> {
+    let mut counts = [0usize; WORD_COUNT_BUCKETS.len() + 1];
+    for work in work_structs {
+        let words = work.word_count();
+        let bucket = WORD_COUNT_BUCKETS.iter().position(| upper | words < *upper).unwrap_or(WORD_COUNT_BUCKETS.len());
+        counts[bucket] += 1;
+    }
+
+    let max_count = counts.iter().copied().max().unwrap_or(0).max(1);
+    counts.iter().enumerate().map(| (bucket, count) | {
+        let label = match bucket {
+            0 => format!("< {}", with_commas(WORD_COUNT_BUCKETS[0])),
+            _ if bucket == WORD_COUNT_BUCKETS.len() => format!("{}+", with_commas(WORD_COUNT_BUCKETS[bucket - 1])),
+            _ => format!("{} – {}", with_commas(WORD_COUNT_BUCKETS[bucket - 1]), with_commas(WORD_COUNT_BUCKETS[bucket])),
+        };
+        HistogramBucket {
+            label,
+            works: *count,
+            percent: count * 100 / max_count,
+        }
+    }).collect()
+}
+
+impl <'a> LibraryStats <'a> {
+
+    // Everything per-category comes from the listings that were already accumulated for the category indexes
+    pub fn new (locale: &'a Locale, words_per_minute: usize, output_name: String, work_structs: &[&WorkStruct], category_listings: &HashMap<Category, HashMap<String, CategoryListing>>) -> Self {
+        let total_chapters: usize = work_structs.iter().map(| work | work.chapters.len()).sum();
+        let total_words: usize = work_structs.iter().map(| work | work.word_count()).sum();
+
+        let mut top_tags = stats_rows(category_listings, &Category::Tags, false);
+        top_tags.truncate(TOP_COUNT);
+        let mut top_relationships = stats_rows(category_listings, &Category::Relationships, false);
+        top_relationships.truncate(TOP_COUNT);
+
+        LibraryStats {
+            locale,
+            output_name,
+            total_works: with_commas(work_structs.len()),
+            total_chapters: with_commas(total_chapters),
+            total_words: with_commas(total_words),
+            reading_time: locale.reading_time(total_words, words_per_minute),
+            word_count_histogram: word_count_histogram(work_structs),
+            ratings: stats_rows(category_listings, &Category::Ratings, false),
+            fandoms: stats_rows(category_listings, &Category::Fandoms, true),
+            authors: stats_rows(category_listings, &Category::Authors, true),
+            top_tags,
+            top_relationships,
+        }
+    }
+}
+
+impl OverridableTemplate for LibraryStats<'_> {
+    const TEMPLATE_PATH: &'static str = "stats.html";
+}
@@ -1,10 +1,263 @@
+use std::collections::HashMap;
+
 use askama::Template;
-use crate::html::types::{Category, Work};
+use crate::html::{process_html::ao3_link_key, titles::sort_key, types::{Anchor, Category, Work, WorkStruct}};
+use serde::Serialize;
+use crate::epub::{file_templating::{category_index::CategoryListing, work::navigation::{ReadingOrder, chapter_page, introduction_page, series_page}}, locale::Locale, template_overrides::OverridableTemplate};
+
+// One entry of the table of contents, before it gets numbered and flattened for the template
+struct NavNode {
+    label: String,
+    src: String,
+    children: Vec<NavNode>,
+}
+
+impl NavNode {
+    fn new (label: impl Into<String>, src: impl Into<String>) -> Self {
+        NavNode { label: label.into(), src: src.into(), children: Vec::new() }
+    }
+
+    fn depth (&self) -> usize {
+        1 + self.children.iter().map(| child | child.depth()).max().unwrap_or(0)
+    }
+}
+
+// One navPoint of toc.ncx, in document order
+// The tree is flattened so the template doesn't need recursion: every entry opens a navPoint, and `closes` is how many
+//      navPoints to close right after it (0 when the next entry is its first child)
+#[derive(Serialize)]
+pub struct NavEntry {
+    pub id: String,
+    pub play_order: usize,
+    pub label: String,
+    pub src: String,
+    pub closes: usize,
+}
+
+// Where a work (or series) goes when it has more than one entry in the category the table of contents is grouped by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MultiValued {
+    // Under the heading of every one of its entries
+    Each,
+    // Under a single "Multiple" heading
+    Multiple,
+}
+
+impl std::str::FromStr for MultiValued {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.trim().to_lowercase()[..] {
+            "each" => Ok(MultiValued::Each),
+            "multiple" => Ok(MultiValued::Multiple),
+            other => Err(format!("Unknown grouping of multi-valued works '{other}'.  Expected one of: each, multiple")),
+        }
+    }
+}
+
+// Which optional entries go in the table of contents
+pub struct TocOptions {
+    // A navPoint for every category listing page, under its category
+    pub listings: bool,
+    // A navPoint for every series page, with the works of the series under it (instead of directly under the works index)
+    pub series: bool,
+    // Nest the works of the works index and of toc.ncx under a heading for each entry of this category
+    //      (each fandom, author, rating, ...) instead of listing them all flat
+    pub group_by: Option<Category>,
+    pub multi_valued: MultiValued,
+}
+
+// Works (and series) nested under one heading of the works index / toc.ncx
+// `heading` is None when the works aren't grouped, otherwise it links (relative to the root of the ePub) to the
+//      category listing page of the heading, or to the category index for the "Multiple" / "Other" headings
+#[derive(Serialize)]
+pub struct WorkGroup <'a> {
+    pub heading: Option<Anchor>,
+    pub works: Vec<&'a Work>,
+}
+
+// Split `works` into the groups configured in `options`, headings sorted by name with "Multiple" and "Other" last
+// A series goes under the entries of all of its works together, so it's never split up between headings
+// Works without any entry in the category (like works without additional tags) go under "Other"
+pub fn group_works <'a> (locale: &Locale, works: &'a [Work], category_listings: &HashMap<Category, HashMap<String, CategoryListing>>, options: &TocOptions) -> Vec<WorkGroup<'a>> {
+    let Some(category) = &options.group_by else {
+        return vec![ WorkGroup { heading: None, works: works.iter().collect() } ];
+    };
+
+    // Links of the category's listing pages, keyed by the link of the entry (the same key the listings use)
+    let listings = category_listings.get(category);
+    let category_index = Anchor { name: String::new(), link: format!("indexes/{category}/index.xhtml") };
+
+    let mut groups: Vec<WorkGroup> = Vec::new();
+    let mut group_indexes: HashMap<String, usize> = HashMap::new();
+    let mut multiple: Vec<&Work> = Vec::new();
+    let mut other: Vec<&Work> = Vec::new();
+    for work in works {
+        let mut entries: Vec<Anchor> = Vec::new();
+        for work_struct in work.work_structs() {
+            for entry in work_struct.category_entries(category) {
+                if !entries.iter().any(| existing | ao3_link_key(&existing.link) == ao3_link_key(&entry.link)) {
+                    entries.push(entry);
+                }
+            }
+        }
+
+        if entries.is_empty() {
+            other.push(work);
+            continue;
+        }
+        if entries.len() > 1 && options.multi_valued == MultiValued::Multiple {
+            multiple.push(work);
+            continue;
+        }
+
+        for entry in entries {
+            let group_index = *group_indexes.entry(String::from(ao3_link_key(&entry.link))).or_insert_with(|| {
+                let link = match listings.and_then(| listings | listings.get(ao3_link_key(&entry.link))) {
+                    Some(listing) => format!("indexes/{category}/{category}-{}-listing.xhtml", listing.id),
+                    None => category_index.link.clone(),
+                };
+                groups.push(WorkGroup { heading: Some(Anchor { name: entry.name.clone(), link }), works: Vec::new() });
+                groups.len() - 1
+            });
+            groups[group_index].works.push(work);
+        }
+    }
 
-#[derive(Template)]
+    groups.sort_by_cached_key(| group | group.heading.as_ref().map(| heading | sort_key(&heading.name)));
+    for (name, works) in [ (locale.multiple, multiple), (locale.other, other) ] {
+        if !works.is_empty() {
+            groups.push(WorkGroup { heading: Some(Anchor { name: String::from(name), ..category_index.clone() }), works });
+        }
+    }
+    groups
+}
+
+#[derive(Template, Serialize)]
 #[template(path = "toc.html")]
 pub struct TableOfContents <'a> {
+    pub locale: &'a Locale,
     pub output_name: String,
-    pub categories: &'a [Category],
-    pub works: &'a Vec<Work>
-}
\ No newline at end of file
+    // Depth of the deepest navPoint, for dtb:depth
+    pub depth: usize,
+    pub entries: Vec<NavEntry>,
+}
+
+fn work_node (work: &WorkStruct) -> NavNode {
+    let mut node = NavNode::new(&work.title, introduction_page(work));
+    node.children = work.chapters.iter().map(| chapter | NavNode::new(&chapter.title, chapter_page(work, chapter.order, 0))).collect();
+    node
+}
+
+// Flatten `nodes` (depth first) into `entries`, leaving the playOrder for `number_play_orders`
+fn flatten (nodes: Vec<NavNode>, entries: &mut Vec<NavEntry>) {
+    for node in nodes {
+        entries.push(NavEntry {
+            id: format!("navpoint-{}", entries.len() + 1),
+            play_order: 0,
+            label: node.label,
+            src: node.src,
+            closes: 0,
+        });
+
+        if node.children.is_empty() {
+            entries.last_mut().unwrap().closes = 1;
+        }
+        else {
+            flatten(node.children, entries);
+            entries.last_mut().unwrap().closes += 1;
+        }
+    }
+}
+
+// playOrder is the order the files are read in, which isn't always the order they're listed in (with --toc_group_by
+//      a work is listed under its heading, wherever that falls)
+// The indexes come first, in the order they're listed, and the content pages follow in `reading_order`, the same
+//      order the navigation bars step through them
+// navPoints that point at the same file share a playOrder, which is how the NCX spec wants it, and every other
+//      file gets the next number, so playOrder always runs 1, 2, 3, ... without gaps
+fn number_play_orders (entries: &mut [NavEntry], reading_order: &ReadingOrder) {
+    let mut srcs: Vec<&str> = Vec::new();
+    for entry in entries.iter() {
+        if !srcs.contains(&&entry.src[..]) {
+            srcs.push(&entry.src);
+        }
+    }
+    // Stable, so the indexes (which aren't in the reading order) keep the order they're listed in
+    srcs.sort_by_key(| src | reading_order.position(src));
+
+    let play_orders: HashMap<String, usize> = srcs.into_iter().enumerate().map(| (index, src) | (String::from(src), index + 1)).collect();
+    for entry in entries {
+        entry.play_order = play_orders[&entry.src];
+    }
+}
+
+impl <'a> TableOfContents <'a> {
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn new (locale: &'a Locale, output_name: String, categories: &[Category], works: &[Work], reading_order: &ReadingOrder, category_listings: &HashMap<Category, HashMap<String, CategoryListing>>, has_volumes: bool, options: &TocOptions) -> Self {
+        // Index -> Category indexes (-> Listings), Library Stats, Volumes
+        let mut index = NavNode::new(locale.index, "indexes/index_index.xhtml");
+        for category in categories {
+            let mut category_node = NavNode::new(locale.category_name(category), format!("indexes/{category}/index.xhtml"));
+
+            // Titles don't have listing pages, the title index links straight to the works
+            if options.listings && *category != Category::Titles && let Some(listings) = category_listings.get(category) {
+                let mut listings: Vec<&CategoryListing> = listings.values().collect();
+                listings.sort_by(| a, b | a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+                category_node.children = listings.into_iter().map(| listing | {
+                    NavNode::new(&listing.name, format!("indexes/{category}/{category}-{}-listing.xhtml", listing.id))
+                }).collect();
+            }
+            index.children.push(category_node);
+        }
+        index.children.push(NavNode::new(locale.library_stats, "indexes/stats.xhtml"));
+        if has_volumes {
+            index.children.push(NavNode::new(locale.volumes, "indexes/volumes_index.xhtml"));
+        }
+
+        // Works Index -> (Headings ->) (Series ->) Works -> Chapters
+        let mut works_index = NavNode::new(locale.works_index, "indexes/works_index.xhtml");
+        for group in group_works(locale, works, category_listings, options) {
+            let mut work_nodes: Vec<NavNode> = Vec::new();
+            for work in group.works {
+                match work {
+                    Work::Single(work_struct) => work_nodes.push(work_node(work_struct)),
+                    Work::Series(series, work_structs) if options.series => {
+                        let mut series_node = NavNode::new(format!("{}: {}", locale.series, series.title), series_page(series));
+                        series_node.children = work_structs.iter().map(work_node).collect();
+                        work_nodes.push(series_node);
+                    },
+                    Work::Series(_, work_structs) => work_nodes.extend(work_structs.iter().map(work_node)),
+                }
+            }
+
+            match group.heading {
+                Some(heading) => {
+                    let mut heading_node = NavNode::new(heading.name, heading.link);
+                    heading_node.children = work_nodes;
+                    works_index.children.push(heading_node);
+                },
+                None => works_index.children.extend(work_nodes),
+            }
+        }
+
+        let nodes = vec![ index, works_index ];
+        let depth = nodes.iter().map(| node | node.depth()).max().unwrap_or(0);
+
+        let mut entries: Vec<NavEntry> = Vec::new();
+        flatten(nodes, &mut entries);
+        number_play_orders(&mut entries, reading_order);
+
+        TableOfContents {
+            locale,
+            output_name,
+            depth,
+            entries,
+        }
+    }
+}
+
+impl OverridableTemplate for TableOfContents<'_> {
+    const TEMPLATE_PATH: &'static str = "toc.html";
+}
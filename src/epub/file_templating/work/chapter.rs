@@ -1,10 +1,22 @@
 use askama::Template;
-use crate::html::types::{Anchor, Chapter};
+use crate::html::types::{Anchor, Chapter, Language};
+use serde::Serialize;
+use crate::epub::{file_templating::work::navigation::NavBar, locale::Locale, template_overrides::OverridableTemplate};
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "work/chapter.html")]
 pub struct WorkChapter <'a> {
+    pub locale: &'a Locale,
     pub work_title: &'a String,
     pub work_author: &'a Anchor,
-    pub chapter: &'a Chapter
-}
\ No newline at end of file
+    pub work_language: &'a Language,
+    pub chapter: &'a Chapter,
+    // Long chapters are split over several files, so this is only the piece of `chapter.data` for this file
+    pub part: &'a str,
+    pub part_number: usize,
+    pub nav: NavBar,
+}
+
+impl OverridableTemplate for WorkChapter<'_> {
+    const TEMPLATE_PATH: &'static str = "work/chapter.html";
+}
@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+
+use askama::Template;
+use crate::{epub::file_templating::{category_index::CategoryListing, work::{introduction::epub_links, series::SeriesInfo}}, html::types::{Anchor, Category, WorkStruct}};
+use serde::Serialize;
+use crate::epub::{file_templating::work::navigation::NavBar, locale::Locale, template_overrides::OverridableTemplate};
+
+// Where to go after a work that's part of a series
+// Only the parts of the series that were downloaded are in the ePub, so the part right after this one might not be
+#[derive(Serialize)]
+pub struct SeriesContinuation {
+    pub series: SeriesInfo,
+    // The next part, when it's in the ePub
+    pub next_part: Option<Anchor>,
+    // Number of the part right after this one, when it's missing from the ePub
+    pub missing_part: Option<usize>,
+    // The first part after the missing one that is in the ePub, if there is one
+    pub next_available: Option<Anchor>,
+}
+
+impl SeriesContinuation {
+    fn new (series: SeriesInfo) -> Self {
+        let part_number = series.part_number;
+        let next = series.parts.iter()
+            .find(| part | part.first > part_number && part.work.is_some());
+        let (next_part, missing_part, next_available) = match next {
+            Some(next) if next.first == part_number + 1 => (next.work.clone(), None, None),
+            Some(next) => (None, Some(part_number + 1), next.work.clone()),
+            // Nothing after this one in the ePub, but AO3 says there's more
+            None if series.total_parts.is_some_and(| total_parts | total_parts > part_number) => (None, Some(part_number + 1), None),
+            // Last part in the ePub, and either the last part of the series or there's no telling
+            None => (None, None, None),
+        };
+
+        SeriesContinuation {
+            series,
+            next_part,
+            missing_part,
+            next_available,
+        }
+    }
+}
+
+// End of work (content/work-<id>/work-<id>-end.xhtml) ->
+//      Page after the last chapter, so it's clear the work is over: final stats, the author, the tags, and for works
+//      of a series, where the series goes from here
+#[derive(Template, Serialize)]
+#[template(path = "work/end.html")]
+pub struct WorkEnd <'a> {
+    pub locale: &'a Locale,
+    pub work: &'a WorkStruct,
+    pub epub_tags_links: Vec<Anchor>,
+    // One for every series the work is part of
+    pub series_continuations: Vec<SeriesContinuation>,
+    pub nav: NavBar,
+}
+
+impl <'a> WorkEnd <'a> {
+    pub fn new (locale: &'a Locale, work: &'a WorkStruct, category_listings: &HashMap<Category, HashMap<String, CategoryListing>>, series_info: Vec<SeriesInfo>, nav: NavBar) -> Self {
+        WorkEnd {
+            locale,
+            work,
+            epub_tags_links: epub_links(work, Category::Tags, category_listings),
+            series_continuations: series_info.into_iter().map(SeriesContinuation::new).collect(),
+            nav,
+        }
+    }
+}
+
+impl OverridableTemplate for WorkEnd<'_> {
+    const TEMPLATE_PATH: &'static str = "work/end.html";
+}
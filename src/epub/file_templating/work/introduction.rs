@@ -1,46 +1,96 @@
 use std::collections::HashMap;
 
 use askama::Template;
-use crate::{epub::file_templating::category_index::CategoryListing, html::types::{Anchor, Category, WorkSeries, WorkStruct}};
+use crate::{epub::file_templating::{category_index::CategoryListing, work::{navigation::{content_link, introduction_page}, series::SeriesInfo}}, html::{process_html::{ao3_link_key, ao3_work_id}, types::{Anchor, AssociationKind, Author, Category, WorkStruct}}};
+use serde::Serialize;
+use crate::epub::{file_templating::work::navigation::NavBar, locale::Locale, template_overrides::OverridableTemplate};
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "work/introduction.html")]
 pub struct WorkIntroduction <'a> {
+    pub locale: &'a Locale,
     pub epub_ratings_links: Vec<Anchor>,
     pub epub_categories_links: Vec<Anchor>,
     pub epub_fandoms_links: Vec<Anchor>,
     pub epub_relationships_links: Vec<Anchor>,
     pub epub_characters_links: Vec<Anchor>,
     pub epub_tags_links: Vec<Anchor>,
+    pub epub_collections_links: Vec<Anchor>,
     pub work: &'a WorkStruct,
-    pub series_info: Option<(&'a WorkSeries, &'a Vec<WorkStruct>)>,
+    // Every series the work is part of, with all of their parts
+    pub series_info: Vec<SeriesInfo>,
+    // Works this one was inspired by, is a translation of, ...
+    pub associations: Vec<AssociationLink>,
+    // "You might also like" -> The most similar other works in this ePub
+    pub recommendations: &'a [Anchor],
+    pub nav: NavBar,
+}
+
+// A related work, linked to its introduction page when it's in this ePub and to AO3 otherwise
+#[derive(Serialize)]
+pub struct AssociationLink {
+    pub kind: AssociationKind,
+    // How it's related, in the ePub's locale ("Inspired by", "A translation of", ...)
+    pub label: &'static str,
+    pub work: Anchor,
+    pub creators: Vec<Author>,
+    pub in_epub: bool,
+}
+
+// Every related work of `work`, with `works_by_ao3_id` being every work in this ePub keyed by its AO3 number
+pub(crate) fn association_links (locale: &Locale, work: &WorkStruct, works_by_ao3_id: &HashMap<&str, &WorkStruct>) -> Vec<AssociationLink> {
+    work.associations.iter().map(| association | {
+        let in_epub = ao3_work_id(&association.work.link).and_then(| id | works_by_ao3_id.get(id));
+        AssociationLink {
+            kind: association.kind,
+            label: locale.association(association.kind),
+            work: match in_epub {
+                Some(other) => Anchor { link: content_link(&introduction_page(other)), name: association.work.name.clone() },
+                None => association.work.clone(),
+            },
+            creators: association.creators.clone(),
+            in_epub: in_epub.is_some(),
+        }
+    }).collect()
+}
+
+// Links from a work's pages to the listing pages of each of its subcategories under `category`
+pub(crate) fn epub_links (work: &WorkStruct, category: Category, category_listings: &HashMap<Category, HashMap<String, CategoryListing>>) -> Vec<Anchor> {
+    work.category_data.get(&category).unwrap().iter().map(| anchor | {
+        let listing_map = category_listings.get(&category).unwrap();
+        let listing_id = listing_map.get(ao3_link_key(&anchor.link)).unwrap().id;
+        let epub_link = format!("../../indexes/{category}/{category}-{listing_id}-listing.xhtml");
+        let link_name = anchor.name.clone();
+        return Anchor {
+            link: epub_link,
+            name: link_name
+        }
+    }).collect()
 }
 
 impl <'a> WorkIntroduction <'a> {
-    pub(crate) fn new(work: &&'a WorkStruct, category_listings: &'a HashMap<Category, HashMap<String, CategoryListing>>, series_info: Option<(&'a WorkSeries, &'a Vec<WorkStruct>)>) -> Self {
-
-        let epub_link_from_category = | work: &WorkStruct, category: Category | -> Vec<Anchor> {
-            work.category_data.get(&category).unwrap().iter().map(| anchor | {
-                let listing_map = category_listings.get(&category).unwrap();
-                let listing_id = listing_map.get(&anchor.link).unwrap().id;
-                let epub_link = format!("../../indexes/{category}/{category}-{listing_id}-listing.xhtml");
-                let link_name = anchor.name.clone();
-                return Anchor {
-                    link: epub_link,
-                    name: link_name
-                }
-            }).collect()
-        };
+    pub(crate) fn new(locale: &'a Locale, work: &&'a WorkStruct, category_listings: &'a HashMap<Category, HashMap<String, CategoryListing>>, series_info: Vec<SeriesInfo>, associations: Vec<AssociationLink>, recommendations: &'a [Anchor], nav: NavBar) -> Self {
+
+        let epub_link_from_category = | work: &WorkStruct, category: Category | epub_links(work, category, category_listings);
 
         Self {
+            locale,
             epub_ratings_links:       epub_link_from_category(&work, Category::Ratings), 
             epub_categories_links:    epub_link_from_category(&work, Category::Categories), 
             epub_fandoms_links:       epub_link_from_category(&work, Category::Fandoms), 
             epub_relationships_links: epub_link_from_category(&work, Category::Relationships), 
             epub_characters_links:    epub_link_from_category(&work, Category::Characters), 
             epub_tags_links:          epub_link_from_category(&work, Category::Tags), 
+            epub_collections_links:   epub_link_from_category(work, Category::Collections), 
             work: work,
-            series_info: series_info
+            series_info: series_info,
+            associations,
+            recommendations,
+            nav,
         }
     }
-}
\ No newline at end of file
+}
+
+impl OverridableTemplate for WorkIntroduction<'_> {
+    const TEMPLATE_PATH: &'static str = "work/introduction.html";
+}
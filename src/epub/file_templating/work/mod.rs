@@ -1,4 +1,6 @@
 pub(crate) mod introduction;
 pub(crate) mod preview;
 pub(crate) mod chapter;
-pub(crate) mod series;
\ No newline at end of file
+pub(crate) mod series;
+pub(crate) mod navigation;
+pub(crate) mod end;
\ No newline at end of file
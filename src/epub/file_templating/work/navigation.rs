@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+use crate::{epub::{file_templating::category_index::CategoryListing, locale::Locale}, html::{process_html::ao3_link_key, types::{Anchor, Category, Work, WorkSeries, WorkStruct}}};
+
+// Where the navigation bars go on the series, introduction, preview, chapter and end of work pages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NavBarPosition {
+    Both,
+    Header,
+    Footer,
+    Hidden,
+}
+
+impl std::str::FromStr for NavBarPosition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.trim().to_lowercase()[..] {
+            "both" => Ok(NavBarPosition::Both),
+            "header" => Ok(NavBarPosition::Header),
+            "footer" => Ok(NavBarPosition::Footer),
+            "none" => Ok(NavBarPosition::Hidden),
+            other => Err(format!("Unknown navigation bar position '{other}'.  Expected one of: both, header, footer, none")),
+        }
+    }
+}
+
+// Links around one content page (templates/work/nav_bar.html)
+// Every content page lives two directories deep (content/work-<id>/, content/series/), so links all start with "../../"
+#[derive(Serialize)]
+pub struct NavBar {
+    // Whether the bar goes at the top and / or the bottom of the page
+    pub header: bool,
+    pub footer: bool,
+    pub previous: Option<Anchor>,
+    pub next: Option<Anchor>,
+    // The page this one belongs to: the work's introduction for previews and chapters, the series page for introductions
+    pub up: Option<Anchor>,
+    pub next_in_series: Option<Anchor>,
+    pub works_index: String,
+    // The category listing the work is filed under (its heading with --toc_group_by, otherwise its first fandom)
+    pub listing: Option<Anchor>,
+}
+
+// Every content page in reading order, which is the order write_epub_files writes them in, and so the order of
+//      the spine: for each work (or series) -> the series page, then for each work -> introduction, preview, chapters,
+//      end of work
+// Chapters that get split over several files only have their first file in here, so previous / next skip a whole chapter
+// Built once per ePub and shared by the navigation bars and toc.ncx, which numbers its content pages in this order
+pub struct ReadingOrder {
+    pages: Vec<Anchor>,
+    positions: HashMap<String, usize>,
+}
+
+pub fn content_link (path: &str) -> String {
+    format!("../../{path}")
+}
+
+pub fn series_page (series: &WorkSeries) -> String {
+    format!("content/series/series-{}.xhtml", series.id)
+}
+
+pub fn introduction_page (work: &WorkStruct) -> String {
+    format!("content/work-{}/work-{}.xhtml", work.id, work.id)
+}
+
+pub fn preview_page (work: &WorkStruct) -> String {
+    format!("content/work-{}/work-{}-preview.xhtml", work.id, work.id)
+}
+
+pub fn end_page (work: &WorkStruct) -> String {
+    format!("content/work-{}/work-{}-end.xhtml", work.id, work.id)
+}
+
+pub fn chapter_page (work: &WorkStruct, chapter_order: usize, part_number: usize) -> String {
+    match part_number {
+        0 => format!("content/work-{}/work-{}-chapter-{chapter_order}.xhtml", work.id, work.id),
+        _ => format!("content/work-{}/work-{}-chapter-{chapter_order}-part-{part_number}.xhtml", work.id, work.id),
+    }
+}
+
+impl ReadingOrder {
+
+    pub fn new (locale: &Locale, works: &[Work]) -> Self {
+        let mut pages: Vec<Anchor> = Vec::new();
+        for work in works {
+            if let Work::Series(series, _) = work {
+                pages.push(Anchor { link: series_page(series), name: format!("{}: {}", locale.series, series.title) });
+            }
+            for work_struct in work.work_structs() {
+                pages.push(Anchor { link: introduction_page(work_struct), name: work_struct.title.clone() });
+                pages.push(Anchor { link: preview_page(work_struct), name: format!("{}: {}", work_struct.title, locale.chapters) });
+                pages.extend(work_struct.chapters.iter().map(| chapter | Anchor {
+                    link: chapter_page(work_struct, chapter.order, 0),
+                    name: chapter.title.clone(),
+                }));
+                pages.push(Anchor { link: end_page(work_struct), name: format!("{} {}", locale.end_of, work_struct.title) });
+            }
+        }
+
+        let positions = pages.iter().enumerate().map(| (index, page) | (page.link.clone(), index)).collect();
+        ReadingOrder { pages, positions }
+    }
+
+    // Where `page` (a path like the ones from `chapter_page`) is in the reading order, if it is a content page
+    pub fn position (&self, page: &str) -> Option<usize> {
+        self.positions.get(page).copied()
+    }
+
+    // Pages right before and right after `page`
+    fn neighbours (&self, page: &str) -> (Option<Anchor>, Option<Anchor>) {
+        let Some(position) = self.position(page) else {
+            return (None, None);
+        };
+
+        let link = | page: &Anchor | Anchor { link: content_link(&page.link), name: page.name.clone() };
+        let previous = position.checked_sub(1).map(| previous | link(&self.pages[previous]));
+        let next = self.pages.get(position + 1).map(link);
+        (previous, next)
+    }
+}
+
+// Builds the navigation bars of the pages of one ePub
+pub struct NavBars <'a> {
+    pub position: NavBarPosition,
+    pub reading_order: &'a ReadingOrder,
+    pub category_listings: &'a HashMap<Category, HashMap<String, CategoryListing<'a>>>,
+    // Category of the "listing it came from" link
+    pub listing_category: Category,
+}
+
+impl NavBars<'_> {
+
+    fn nav_bar (&self, page: &str) -> NavBar {
+        let (previous, next) = self.reading_order.neighbours(page);
+        NavBar {
+            header: matches!(self.position, NavBarPosition::Both | NavBarPosition::Header),
+            footer: matches!(self.position, NavBarPosition::Both | NavBarPosition::Footer),
+            previous,
+            next,
+            up: None,
+            next_in_series: None,
+            works_index: content_link("indexes/works_index.xhtml"),
+            listing: None,
+        }
+    }
+
+    fn listing (&self, work: &WorkStruct) -> Option<Anchor> {
+        let category = &self.listing_category;
+        let entry = work.category_entries(category).into_iter().next()?;
+        let listing = self.category_listings.get(category)?.get(ao3_link_key(&entry.link))?;
+        Some(Anchor {
+            link: content_link(&format!("indexes/{category}/{category}-{}-listing.xhtml", listing.id)),
+            name: listing.name.clone(),
+        })
+    }
+
+    fn next_in_series (work: &WorkStruct, series: Option<(&WorkSeries, &Vec<WorkStruct>)>) -> Option<Anchor> {
+        let (_, series_works) = series?;
+        let position = series_works.iter().position(| series_work | series_work.id == work.id)?;
+        series_works.get(position + 1).map(| next | Anchor { link: content_link(&introduction_page(next)), name: next.title.clone() })
+    }
+
+    pub fn series (&self, series: &WorkSeries) -> NavBar {
+        self.nav_bar(&series_page(series))
+    }
+
+    pub fn introduction (&self, work: &WorkStruct, series: Option<(&WorkSeries, &Vec<WorkStruct>)>, locale: &Locale) -> NavBar {
+        NavBar {
+            up: series.map(| (work_series, _) | Anchor {
+                link: content_link(&series_page(work_series)),
+                name: format!("{}: {}", locale.series, work_series.title),
+            }),
+            next_in_series: NavBars::next_in_series(work, series),
+            listing: self.listing(work),
+            ..self.nav_bar(&introduction_page(work))
+        }
+    }
+
+    pub fn preview (&self, work: &WorkStruct, series: Option<(&WorkSeries, &Vec<WorkStruct>)>) -> NavBar {
+        NavBar {
+            up: Some(Anchor { link: content_link(&introduction_page(work)), name: work.title.clone() }),
+            next_in_series: NavBars::next_in_series(work, series),
+            listing: self.listing(work),
+            ..self.nav_bar(&preview_page(work))
+        }
+    }
+
+    // The end page links to the next part of the series itself, so it doesn't get `next_in_series`
+    pub fn end (&self, work: &WorkStruct) -> NavBar {
+        NavBar {
+            up: Some(Anchor { link: content_link(&introduction_page(work)), name: work.title.clone() }),
+            listing: self.listing(work),
+            ..self.nav_bar(&end_page(work))
+        }
+    }
+
+    // Every part of a split chapter links to the parts right next to it, and only the first and last parts link out
+    //      to the previous / next chapter
+    pub fn chapter (&self, work: &WorkStruct, series: Option<(&WorkSeries, &Vec<WorkStruct>)>, chapter_order: usize, part_number: usize, part_count: usize) -> NavBar {
+        let mut nav_bar = NavBar {
+            up: Some(Anchor { link: content_link(&introduction_page(work)), name: work.title.clone() }),
+            next_in_series: NavBars::next_in_series(work, series),
+            listing: self.listing(work),
+            ..self.nav_bar(&chapter_page(work, chapter_order, 0))
+        };
+
+        let chapter_title = work.chapters.iter().find(| chapter | chapter.order == chapter_order).map_or(String::new(), | chapter | chapter.title.clone());
+        if part_number > 0 {
+            nav_bar.previous = Some(Anchor { link: content_link(&chapter_page(work, chapter_order, part_number - 1)), name: chapter_title.clone() });
+        }
+        if part_number + 1 < part_count {
+            nav_bar.next = Some(Anchor { link: content_link(&chapter_page(work, chapter_order, part_number + 1)), name: chapter_title });
+        }
+        nav_bar
+    }
+}
@@ -1,8 +1,16 @@
 use askama::Template;
 use crate::html::types::WorkStruct;
+use serde::Serialize;
+use crate::epub::{file_templating::work::navigation::NavBar, locale::Locale, template_overrides::OverridableTemplate};
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "work/preview.html")]
 pub struct WorkPreview <'a> {
+    pub locale: &'a Locale,
     pub work: &'a WorkStruct,
-}
\ No newline at end of file
+    pub nav: NavBar,
+}
+
+impl OverridableTemplate for WorkPreview<'_> {
+    const TEMPLATE_PATH: &'static str = "work/preview.html";
+}
@@ -1,9 +1,142 @@
+use std::collections::HashMap;
+
 use askama::Template;
-use crate::html::types::{WorkSeries, WorkStruct};
+use crate::html::{process_html::ao3_link_key, types::{Anchor, Work, WorkSeries, WorkStruct}};
+use serde::Serialize;
+use crate::epub::{file_templating::work::navigation::NavBar, locale::Locale, template_overrides::OverridableTemplate};
+
+// One line of a series' list of parts
+// `work` is None for a run of parts that weren't downloaded (parts `first` to `last`), which gets a placeholder
+#[derive(Serialize, Clone)]
+pub struct SeriesPart {
+    pub first: usize,
+    pub last: usize,
+    pub work: Option<Anchor>,
+}
+
+// One of the series a work is part of
+// Links are relative to the work pages (content/work-<id>/...)
+#[derive(Serialize, Clone)]
+pub struct SeriesInfo {
+    pub title: String,
+    // The series on AO3
+    pub link: String,
+    // The series page in the ePub, if the series got one (see `group_into_series`)
+    pub page: Option<String>,
+    pub part_number: usize,
+    pub total_parts: Option<usize>,
+    pub parts: Vec<SeriesPart>,
+}
+
+struct SeriesEntry <'a> {
+    title: String,
+    total_parts: Option<usize>,
+    page: Option<String>,
+    // Part number and work, sorted by part number
+    works: Vec<(usize, &'a WorkStruct)>,
+}
+
+// Every series any work of the ePub is part of, with all of the works of the ePub that are part of it
+// Series are keyed by `ao3_link_key`, since works from downloads and from saved pages link to them differently
+// A work that is part of several series only sits under one `Work::Series`, so this is what the other series use
+//      to list it
+pub struct SeriesDirectory <'a> {
+    series: HashMap<String, SeriesEntry<'a>>,
+}
+
+impl <'a> SeriesDirectory <'a> {
+
+    pub fn new (works: &'a [Work]) -> Self {
+        let pages: HashMap<&str, String> = works.iter().filter_map(| work | match work {
+            Work::Series(work_series, _) => Some((ao3_link_key(&work_series.link), format!("../../content/series/series-{}.xhtml", work_series.id))),
+            Work::Single(_) => None,
+        }).collect();
+
+        let mut series: HashMap<String, SeriesEntry> = HashMap::new();
+        for work_struct in works.iter().flat_map(| work | work.work_structs()) {
+            for work_series in &work_struct.series {
+                let entry = series.entry(String::from(ao3_link_key(&work_series.link))).or_insert_with(|| SeriesEntry {
+                    title: work_series.name.clone(),
+                    total_parts: None,
+                    page: pages.get(ao3_link_key(&work_series.link)).cloned(),
+                    works: Vec::new(),
+                });
+                entry.total_parts = entry.total_parts.max(work_series.total_parts);
+                entry.works.push((work_series.part_number, work_struct));
+            }
+        }
 
-#[derive(Template)]
+        for entry in series.values_mut() {
+            entry.works.sort_by_key(| (part_number, work_struct) | (*part_number, work_struct.id));
+        }
+        SeriesDirectory { series }
+    }
+
+    // Every part of the series at `series_link`, from part 1 up to the last part on AO3 (or the last downloaded part,
+    //      when AO3 didn't say how many there are)
+    // Runs of parts that weren't downloaded are squashed into a single placeholder, so a 300 part series with two
+    //      downloaded works doesn't list 298 placeholders
+    pub fn parts (&self, series_link: &str) -> Vec<SeriesPart> {
+        let Some(entry) = self.series.get(ao3_link_key(series_link)) else {
+            return Vec::new();
+        };
+
+        let last_downloaded = entry.works.last().map_or(0, | (part_number, _) | *part_number);
+        let last_part = entry.total_parts.unwrap_or(0).max(last_downloaded);
+
+        let mut parts: Vec<SeriesPart> = Vec::new();
+        let mut works = entry.works.iter().peekable();
+        for part_number in 1..=last_part {
+            let mut downloaded = false;
+            while let Some((_, work_struct)) = works.next_if(| (work_part_number, _) | *work_part_number <= part_number) {
+                downloaded = true;
+                parts.push(SeriesPart {
+                    first: part_number,
+                    last: part_number,
+                    work: Some(Anchor {
+                        link: format!("../../content/work-{}/work-{}.xhtml", work_struct.id, work_struct.id),
+                        name: work_struct.title.clone(),
+                    }),
+                });
+            }
+
+            if !downloaded {
+                match parts.last_mut() {
+                    Some(placeholder) if placeholder.work.is_none() => placeholder.last = part_number,
+                    _ => parts.push(SeriesPart { first: part_number, last: part_number, work: None }),
+                }
+            }
+        }
+        parts
+    }
+
+    // Every series `work` is part of, in the order AO3 lists them
+    pub fn series_info (&self, work: &WorkStruct) -> Vec<SeriesInfo> {
+        work.series.iter().filter_map(| work_series | {
+            let entry = self.series.get(ao3_link_key(&work_series.link))?;
+            Some(SeriesInfo {
+                title: entry.title.clone(),
+                link: work_series.link.clone(),
+                page: entry.page.clone(),
+                part_number: work_series.part_number,
+                total_parts: entry.total_parts,
+                parts: self.parts(&work_series.link),
+            })
+        }).collect()
+    }
+}
+
+#[derive(Template, Serialize)]
 #[template(path = "work/series.html")]
 pub struct SeriesTemplate <'a> {
+    pub locale: &'a Locale,
     pub series: &'a WorkSeries,
     pub works: &'a Vec<WorkStruct>,
-}
\ No newline at end of file
+    // Every part of the series, with placeholders for the ones that weren't downloaded
+    pub parts: Vec<SeriesPart>,
+    pub nav: NavBar,
+}
+
+impl OverridableTemplate for SeriesTemplate<'_> {
+    const TEMPLATE_PATH: &'static str = "work/series.html";
+}
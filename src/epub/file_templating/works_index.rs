@@ -1,10 +1,19 @@
 use askama::Template;
 use crate::html::types::{Category, Work};
+use serde::Serialize;
+use crate::epub::{file_templating::toc::WorkGroup, locale::Locale, template_overrides::OverridableTemplate};
 
-#[derive(Template)]
+#[derive(Template, Serialize)]
 #[template(path = "works_index.html")]
 pub struct WorksIndex <'a> {
+    pub locale: &'a Locale,
     pub output_name: String,
     pub categories: &'a [Category],
-    pub works: &'a Vec<Work>
-}
\ No newline at end of file
+    pub works: &'a Vec<Work>,
+    // `works` split under the headings of --toc_group_by (a single group without a heading when not grouped)
+    pub groups: Vec<WorkGroup<'a>>,
+}
+
+impl OverridableTemplate for WorksIndex<'_> {
+    const TEMPLATE_PATH: &'static str = "works_index.html";
+}
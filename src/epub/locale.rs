@@ -0,0 +1,407 @@
+use std::collections::HashMap;
+use serde::Serialize;
+use crate::html::types::{AssociationKind, Category};
+
+// Every label the generated pages use, in one language
+// Selected with --locale; the labels are what gets shown around the works, the works themselves
+//      keep whatever language they were written in
+#[derive(Debug, Serialize)]
+pub struct Locale {
+    // BCP 47 code for lang / xml:lang / dc:language
+    pub code: &'static str,
+    // "rtl" or "ltr", for the dir attribute
+    pub dir: &'static str,
+
+    pub index: &'static str,
+    pub works_index: &'static str,
+    pub works: &'static str,
+    pub table_of_contents: &'static str,
+    pub listing: &'static str,
+    pub preface: &'static str,
+    pub summary: &'static str,
+    pub no_summary: &'static str,
+    pub chapters: &'static str,
+    pub chapter_summary: &'static str,
+    pub series: &'static str,
+    pub part: &'static str,
+    pub this_work_is_part: &'static str,
+    pub of_series: &'static str,
+    pub by: &'static str,
+    pub posted_originally_on: &'static str,
+    pub archive_of_our_own: &'static str,
+    pub posted_at: &'static str,
+    pub additional_tags: &'static str,
+    pub language: &'static str,
+    pub stats: &'static str,
+    pub words: &'static str,
+    pub volumes: &'static str,
+    pub this_volume: &'static str,
+    pub romantic: &'static str,
+    pub platonic: &'static str,
+    pub cover: &'static str,
+    pub library_stats: &'static str,
+    pub total_works: &'static str,
+    pub total_chapters: &'static str,
+    pub total_words: &'static str,
+    pub reading_time: &'static str,
+    pub word_count_histogram: &'static str,
+    pub top_tags: &'static str,
+    pub top_relationships: &'static str,
+    // Reading times, singular and plural
+    pub hour: &'static str,
+    pub hours: &'static str,
+    pub minute: &'static str,
+    pub minutes: &'static str,
+    pub looks_incomplete: &'static str,
+    pub on_ao3: &'static str,
+    pub in_download: &'static str,
+    pub you_might_also_like: &'static str,
+    pub frequently_appears_with: &'static str,
+    pub previous_page: &'static str,
+    pub next_page: &'static str,
+    // Headings of the works index for works with several entries / no entry in the category it's grouped by
+    pub multiple: &'static str,
+    pub other: &'static str,
+    pub next_in_series: &'static str,
+    // End of work page
+    pub end_of: &'static str,
+    pub next_part: &'static str,
+    pub missing_from_epub: &'static str,
+    pub last_part_in_epub: &'static str,
+    pub series_on_ao3: &'static str,
+    // Series pages
+    pub parts_on_ao3: &'static str,
+    pub not_in_epub: &'static str,
+    pub series_begun: &'static str,
+    pub series_updated: &'static str,
+    pub notes: &'static str,
+    pub complete: &'static str,
+    pub yes: &'static str,
+    pub no: &'static str,
+    // Gifts and related works on introduction pages
+    pub gift_for: &'static str,
+    pub related_works: &'static str,
+    pub inspired_by: &'static str,
+    pub translation_of: &'static str,
+    pub podfic_of: &'static str,
+    pub translation: &'static str,
+    pub inspired: &'static str,
+    pub page: &'static str,
+
+    // Display names of each category, keyed by the category's `Display` string
+    pub category_names: HashMap<String, &'static str>,
+}
+
+impl Locale {
+
+    // Estimated time to read `words` words at `words_per_minute`, like "3 hours 20 minutes" (or just "20 minutes"
+    //      for anything under an hour, and "2 hours" for whole hours)
+    // Nothing to read at all still shows as "< 1 minute" rather than 0
+    pub fn reading_time (&self, words: usize, words_per_minute: usize) -> String {
+        let total_minutes = words.div_ceil(words_per_minute.max(1));
+        let (hours, minutes) = (total_minutes / 60, total_minutes % 60);
+        let hours_label = match hours { 1 => self.hour, _ => self.hours };
+        let minutes_label = match minutes { 1 => self.minute, _ => self.minutes };
+        match (hours, minutes) {
+            (0, 0) => format!("< 1 {}", self.minute),
+            (0, _) => format!("{minutes} {minutes_label}"),
+            (_, 0) => format!("{hours} {hours_label}"),
+            _ => format!("{hours} {hours_label} {minutes} {minutes_label}"),
+        }
+    }
+
+    // Display name of a category in this locale
+    pub fn category_name (&self, category: impl std::fmt::Display) -> &'static str {
+        let category = category.to_string();
+        self.category_names.get(&category).copied().unwrap_or("")
+    }
+
+    // How a related work is related to the one on the page, like "Inspired by"
+    pub fn association (&self, kind: AssociationKind) -> &'static str {
+        match kind {
+            AssociationKind::InspiredBy => self.inspired_by,
+            AssociationKind::TranslationOf => self.translation_of,
+            AssociationKind::PodficOf => self.podfic_of,
+            AssociationKind::Translation => self.translation,
+            AssociationKind::Inspired => self.inspired,
+        }
+    }
+
+    fn category_names (names: [&'static str; 9]) -> HashMap<String, &'static str> {
+        [
+            Category::Titles,
+            Category::Ratings,
+            Category::Categories,
+            Category::Fandoms,
+            Category::Relationships,
+            Category::Characters,
+            Category::Tags,
+            Category::Collections,
+            Category::Authors,
+        ].iter().zip(names).map(| (category, name) | (category.to_string(), name)).collect()
+    }
+
+    pub fn english () -> Self {
+        Locale {
+            code: "en",
+            dir: "ltr",
+            index: "Index",
+            works_index: "Works Index",
+            works: "Works",
+            table_of_contents: "Table of Contents",
+            listing: "Listing",
+            preface: "Preface",
+            summary: "Summary",
+            no_summary: "No Summary",
+            chapters: "Chapters",
+            chapter_summary: "Chapter Summary",
+            series: "Series",
+            part: "Part",
+            this_work_is_part: "This work is part",
+            of_series: "of series",
+            by: "by",
+            posted_originally_on: "Posted originally on the",
+            archive_of_our_own: "Archive of Our Own",
+            posted_at: "at",
+            additional_tags: "Additional Tags",
+            language: "Language",
+            stats: "Stats",
+            words: "Words",
+            volumes: "Volumes",
+            this_volume: "This Volume",
+            romantic: "Romantic",
+            platonic: "Platonic",
+            cover: "Cover",
+            library_stats: "Library Stats",
+            total_works: "Total Works",
+            total_chapters: "Total Chapters",
+            total_words: "Total Words",
+            reading_time: "Estimated Reading Time",
+            word_count_histogram: "Works by Length (Words)",
+            top_tags: "Most Common Tags",
+            top_relationships: "Most Common Relationships",
+            hour: "hour",
+            hours: "hours",
+            minute: "minute",
+            minutes: "minutes",
+            looks_incomplete: "This download looks incomplete, part of the work may be missing.",
+            on_ao3: "On AO3",
+            in_download: "In this download",
+            you_might_also_like: "You might also like",
+            frequently_appears_with: "Frequently appears with",
+            previous_page: "Previous",
+            next_page: "Next",
+            multiple: "Multiple",
+            other: "Other",
+            next_in_series: "Next in series",
+            end_of: "End of",
+            next_part: "Next part",
+            missing_from_epub: "is not in this ePub",
+            last_part_in_epub: "This is the last part of the series in this ePub.",
+            series_on_ao3: "The whole series on AO3",
+            parts_on_ao3: "Parts on AO3",
+            not_in_epub: "Not in this ePub",
+            series_begun: "Series Begun",
+            series_updated: "Series Updated",
+            notes: "Notes",
+            complete: "Complete",
+            yes: "Yes",
+            no: "No",
+            gift_for: "For",
+            related_works: "Related Works",
+            inspired_by: "Inspired by",
+            translation_of: "A translation of",
+            podfic_of: "A podfic of",
+            translation: "Translated as",
+            inspired: "Inspired",
+            page: "Page",
+            category_names: Locale::category_names([ "Titles", "Ratings", "Categories", "Fandoms", "Relationships", "Characters", "Tags", "Collections", "Authors" ]),
+        }
+    }
+
+    pub fn spanish () -> Self {
+        Locale {
+            code: "es",
+            dir: "ltr",
+            index: "Índice",
+            works_index: "Índice de obras",
+            works: "Obras",
+            table_of_contents: "Tabla de contenidos",
+            listing: "Listado",
+            preface: "Prefacio",
+            summary: "Resumen",
+            no_summary: "Sin resumen",
+            chapters: "Capítulos",
+            chapter_summary: "Resumen del capítulo",
+            series: "Serie",
+            part: "Parte",
+            this_work_is_part: "Esta obra es la parte",
+            of_series: "de la serie",
+            by: "por",
+            posted_originally_on: "Publicado originalmente en",
+            archive_of_our_own: "Archive of Our Own",
+            posted_at: "en",
+            additional_tags: "Etiquetas adicionales",
+            language: "Idioma",
+            stats: "Estadísticas",
+            words: "Palabras",
+            volumes: "Volúmenes",
+            this_volume: "Este volumen",
+            romantic: "Románticas",
+            platonic: "Platónicas",
+            cover: "Portada",
+            library_stats: "Estadísticas de la biblioteca",
+            total_works: "Total de obras",
+            total_chapters: "Total de capítulos",
+            total_words: "Total de palabras",
+            reading_time: "Tiempo de lectura estimado",
+            word_count_histogram: "Obras por extensión (palabras)",
+            top_tags: "Etiquetas más comunes",
+            top_relationships: "Relaciones más comunes",
+            hour: "hora",
+            hours: "horas",
+            minute: "minuto",
+            minutes: "minutos",
+            looks_incomplete: "Esta descarga parece incompleta, puede faltar parte de la obra.",
+            on_ao3: "En AO3",
+            in_download: "En esta descarga",
+            you_might_also_like: "También te puede gustar",
+            frequently_appears_with: "Aparece a menudo con",
+            previous_page: "Anterior",
+            next_page: "Siguiente",
+            multiple: "Varias",
+            other: "Otras",
+            next_in_series: "Siguiente de la serie",
+            end_of: "Fin de",
+            next_part: "Siguiente parte",
+            missing_from_epub: "no está en este ePub",
+            last_part_in_epub: "Esta es la última parte de la serie en este ePub.",
+            series_on_ao3: "La serie completa en AO3",
+            parts_on_ao3: "Partes en AO3",
+            not_in_epub: "No está en este ePub",
+            series_begun: "Serie comenzada",
+            series_updated: "Serie actualizada",
+            notes: "Notas",
+            complete: "Completa",
+            yes: "Sí",
+            no: "No",
+            gift_for: "Para",
+            related_works: "Obras relacionadas",
+            inspired_by: "Inspirada en",
+            translation_of: "Una traducción de",
+            podfic_of: "Un podfic de",
+            translation: "Traducida como",
+            inspired: "Inspiró",
+            page: "Página",
+            category_names: Locale::category_names([ "Títulos", "Clasificaciones", "Categorías", "Fandoms", "Relaciones", "Personajes", "Etiquetas", "Colecciones", "Autores" ]),
+        }
+    }
+
+    pub fn arabic () -> Self {
+        Locale {
+            code: "ar",
+            dir: "rtl",
+            index: "الفهرس",
+            works_index: "فهرس الأعمال",
+            works: "الأعمال",
+            table_of_contents: "جدول المحتويات",
+            listing: "القائمة",
+            preface: "تمهيد",
+            summary: "الملخص",
+            no_summary: "لا يوجد ملخص",
+            chapters: "الفصول",
+            chapter_summary: "ملخص الفصل",
+            series: "السلسلة",
+            part: "الجزء",
+            this_work_is_part: "هذا العمل هو الجزء",
+            of_series: "من السلسلة",
+            by: "بقلم",
+            posted_originally_on: "نُشر في الأصل على",
+            archive_of_our_own: "Archive of Our Own",
+            posted_at: "على",
+            additional_tags: "وسوم إضافية",
+            language: "اللغة",
+            stats: "الإحصائيات",
+            words: "الكلمات",
+            volumes: "المجلدات",
+            this_volume: "هذا المجلد",
+            romantic: "رومانسية",
+            platonic: "أفلاطونية",
+            cover: "الغلاف",
+            library_stats: "إحصائيات المكتبة",
+            total_works: "إجمالي الأعمال",
+            total_chapters: "إجمالي الفصول",
+            total_words: "إجمالي الكلمات",
+            reading_time: "وقت القراءة المقدر",
+            word_count_histogram: "الأعمال حسب الطول (بالكلمات)",
+            top_tags: "الوسوم الأكثر شيوعًا",
+            top_relationships: "العلاقات الأكثر شيوعًا",
+            hour: "ساعة",
+            hours: "ساعات",
+            minute: "دقيقة",
+            minutes: "دقائق",
+            looks_incomplete: "يبدو أن هذا التنزيل غير مكتمل، قد يكون جزء من العمل مفقودًا.",
+            on_ao3: "على AO3",
+            in_download: "في هذا التنزيل",
+            you_might_also_like: "قد يعجبك أيضًا",
+            frequently_appears_with: "يظهر كثيرًا مع",
+            previous_page: "السابق",
+            next_page: "التالي",
+            multiple: "متعددة",
+            other: "أخرى",
+            next_in_series: "التالي في السلسلة",
+            end_of: "نهاية",
+            next_part: "الجزء التالي",
+            missing_from_epub: "غير موجود في هذا الكتاب",
+            last_part_in_epub: "هذا هو الجزء الأخير من السلسلة في هذا الكتاب.",
+            series_on_ao3: "السلسلة كاملة على AO3",
+            parts_on_ao3: "الأجزاء على AO3",
+            not_in_epub: "غير موجود في هذا الكتاب",
+            series_begun: "بدأت السلسلة",
+            series_updated: "حُدّثت السلسلة",
+            notes: "ملاحظات",
+            complete: "مكتملة",
+            yes: "نعم",
+            no: "لا",
+            gift_for: "إهداء إلى",
+            related_works: "أعمال ذات صلة",
+            inspired_by: "مستوحى من",
+            translation_of: "ترجمة لـ",
+            podfic_of: "بودفيك لـ",
+            translation: "مترجم باسم",
+            inspired: "ألهم",
+            page: "صفحة",
+            category_names: Locale::category_names([ "العناوين", "التصنيفات العمرية", "الفئات", "الفاندومات", "العلاقات", "الشخصيات", "الوسوم", "المجموعات", "المؤلفون" ]),
+        }
+    }
+}
+
+impl std::str::FromStr for Locale {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.trim().to_lowercase()[..] {
+            "en" | "english" => Ok(Locale::english()),
+            "es" | "spanish" | "español" => Ok(Locale::spanish()),
+            "ar" | "arabic" | "العربية" => Ok(Locale::arabic()),
+            other => Err(format!("Unknown locale '{other}'.  Expected one of: en, es, ar")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reading_times () {
+        let locale = Locale::english();
+        assert_eq!(locale.reading_time(0, 250), "< 1 minute");
+        assert_eq!(locale.reading_time(1, 250), "1 minute");
+        assert_eq!(locale.reading_time(500, 250), "2 minutes");
+        assert_eq!(locale.reading_time(250 * 60, 250), "1 hour");
+        assert_eq!(locale.reading_time(250 * 120, 250), "2 hours");
+        assert_eq!(locale.reading_time(250 * 61, 250), "1 hour 1 minute");
+        assert_eq!(locale.reading_time(250 * 200, 250), "3 hours 20 minutes");
+    }
+}
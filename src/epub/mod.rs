@@ -1,2 +1,6 @@
+pub(crate) mod book;
 pub(crate) mod file_templating;
-pub(crate) mod write_epub_files;
\ No newline at end of file
+pub(crate) mod locale;
+pub(crate) mod recommendations;
+pub(crate) mod write_epub_files;
+pub(crate) mod template_overrides;
\ No newline at end of file
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+
+use crate::html::{process_html::ao3_link_key, types::{Anchor, Category, WorkStruct}};
+
+// The categories that are compared between works, and the default weight of each
+// Ratings barely say anything about whether someone will like a work (most of an anthology is usually
+//      one or two ratings), so they count very little
+const DEFAULT_WEIGHTS: [(Category, f64); 7] = [
+    (Category::Fandoms, 3.0),
+    (Category::Relationships, 3.0),
+    (Category::Characters, 2.0),
+    (Category::Tags, 1.0),
+    (Category::Authors, 1.0),
+    (Category::Categories, 0.5),
+    (Category::Ratings, 0.1),
+];
+
+// How much each category counts towards the similarity of two works
+// Set with --recommendation_weights, as a comma separated list of category=weight (like "tags=2,ratings=0")
+// Categories that aren't in the list keep their default weight
+#[derive(Debug, Clone)]
+pub struct RecommendationWeights {
+    weights: Vec<(Category, f64)>,
+}
+
+impl Default for RecommendationWeights {
+    fn default () -> Self {
+        RecommendationWeights { weights: DEFAULT_WEIGHTS.to_vec() }
+    }
+}
+
+impl std::str::FromStr for RecommendationWeights {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut weights = RecommendationWeights::default();
+        for entry in s.split(',').map(| entry | entry.trim()).filter(| entry | !entry.is_empty()) {
+            let Some((category, weight)) = entry.split_once('=') else {
+                return Err(format!("Expected category=weight, got '{entry}'"));
+            };
+
+            let category: Category = category.trim().parse()?;
+            let weight: f64 = weight.trim().parse().map_err(| _ | format!("'{}' is not a number", weight.trim()))?;
+            if weight < 0.0 {
+                return Err(format!("The weight of {category} can't be negative"));
+            }
+
+            match weights.weights.iter_mut().find(| (existing, _) | *existing == category) {
+                Some((_, existing_weight)) => *existing_weight = weight,
+                None => return Err(format!("{category} can't be used for recommendations")),
+            }
+        }
+        Ok(weights)
+    }
+}
+
+// Links of every entry of every weighted category of a work, sorted so that overlaps can be found with a merge
+struct WorkTags <'a> {
+    work: &'a WorkStruct,
+    tags: Vec<Vec<String>>,
+}
+
+// Jaccard index of two sorted lists: how many entries they share out of how many entries they have between them
+fn jaccard (a: &[String], b: &[String]) -> f64 {
+    if a.is_empty() && b.is_empty() {
+        return 0.0;
+    }
+
+    let (mut i, mut j, mut shared) = (0, 0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                shared += 1;
+                i += 1;
+                j += 1;
+            },
+        }
+    }
+    shared as f64 / (a.len() + b.len() - shared) as f64
+}
+
+impl RecommendationWeights {
+
+    // Similarity of two works from 0 to 1
+    // Weighted average of the Jaccard index of each category, skipping categories that neither work has any
+    //      entries in (so two works without any additional tags aren't counted as having nothing in common)
+    fn similarity (&self, a: &WorkTags, b: &WorkTags) -> f64 {
+        let mut score = 0.0;
+        let mut total_weight = 0.0;
+        for (index, (_, weight)) in self.weights.iter().enumerate() {
+            if a.tags[index].is_empty() && b.tags[index].is_empty() {
+                continue;
+            }
+            score += weight * jaccard(&a.tags[index], &b.tags[index]);
+            total_weight += weight;
+        }
+
+        match total_weight > 0.0 {
+            true => score / total_weight,
+            false => 0.0,
+        }
+    }
+
+    // For every work, links to the `count` most similar other works, keyed by work id
+    // Works of the same series are left out since the introduction page already lists those, and so are works that
+    //      have nothing at all in common
+    // Links are relative to the work introduction pages (content/work-<id>/work-<id>.xhtml)
+    pub fn recommend (&self, work_structs: &[&WorkStruct], count: usize) -> HashMap<usize, Vec<Anchor>> {
+        if count == 0 {
+            return HashMap::new();
+        }
+
+        let work_tags: Vec<WorkTags> = work_structs.iter().map(| work | WorkTags {
+            work,
+            tags: self.weights.iter().map(| (category, _) | {
+                let mut links: Vec<String> = work.category_entries(category).into_iter().map(| anchor | String::from(ao3_link_key(&anchor.link))).collect();
+                links.sort();
+                links.dedup();
+                links
+            }).collect(),
+        }).collect();
+
+        work_tags.iter().map(| a | {
+            let mut scores: Vec<(f64, &WorkStruct)> = work_tags.iter()
+                .filter(| b | b.work.id != a.work.id)
+                .filter(| b | !a.work.series.iter().any(| a_series | b.work.series.iter().any(| b_series | ao3_link_key(&a_series.link) == ao3_link_key(&b_series.link))))
+                .map(| b | (self.similarity(a, b), b.work))
+                .filter(| (score, _) | *score > 0.0)
+                .collect();
+
+            // Most similar first, ties broken by id so the order is the same every time the ePub is built
+            scores.sort_by(| (a_score, a_work), (b_score, b_work) | b_score.total_cmp(a_score).then_with(|| a_work.id.cmp(&b_work.id)));
+
+            let recommendations = scores.into_iter().take(count).map(| (_, work) | Anchor {
+                link: format!("../../content/work-{}/work-{}.xhtml", work.id, work.id),
+                name: work.title.clone(),
+            }).collect();
+            (a.work.id, recommendations)
+        }).collect()
+    }
+}
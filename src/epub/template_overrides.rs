@@ -0,0 +1,140 @@
+use std::{collections::HashSet, fs, path::{Path, PathBuf}};
+use serde::Serialize;
+use tera::{Context, Tera};
+
+// Every page of the ePub is an askama template compiled into the program
+// With --templates, any of those pages can be replaced at runtime by a Tera (https://keats.github.io/tera/)
+//      template with the same relative path inside of the --templates directory
+//      (for example `<templates dir>/work/introduction.html` replaces the work introduction page)
+// Pages that aren't overridden fall back to the built-in askama templates
+//
+// The Tera context of a page is the template struct itself, so the fields are the same ones the built-in
+//      templates (in the repo's templates/ directory, which make good starting points) use
+// Every page except content_opf.html and cover.svg also gets `locale`, the labels selected with --locale
+//      (`locale.code`, `locale.dir`, `locale.index`, `locale.summary`, ... see src/epub/locale.rs)
+//
+//      index_index.html                output_name, categories, has_volumes
+//      works_index.html                output_name, categories, works, groups (works under the headings of
+//                                      --toc_group_by, one group without a heading when not grouped)
+//      volumes_index.html              output_name, current_volume, volumes
+//      category-index.html             category, subcategory, subindexes, jump_letters, sections, pagination
+//      category-listing-index.html     category, listing_name, listing, members, co_occurrences, pagination
+//      toc.html                        output_name, depth, entries (flattened navPoints, each followed by `closes`
+//                                      closing </navPoint> tags)
+//      stats.html                      output_name, total_works, total_chapters, total_words, reading_time,
+//                                      word_count_histogram, ratings, fandoms, authors, top_tags, top_relationships
+//      content_opf.html                title, title_sort, language, cover_title, identifier, creators, series, description,
+//                                      published, modified, xhtmls, resources, cover_page
+//      cover.svg                       background, title_lines, detail_lines, footer
+//      cover.html                      title, image_href
+//      work/introduction.html          epub_ratings_links, epub_categories_links, epub_fandoms_links,
+//                                      epub_relationships_links, epub_characters_links, epub_tags_links,
+//                                      epub_collections_links, work, series_info (every series the work is part
+//                                      of: title, link, page, part_number, total_parts, parts), associations
+//                                      (kind, label, work, creators, in_epub), recommendations, nav
+//                                      (`work.gifts` are the recipients, the ones without an AO3 account have an
+//                                      empty link)
+//                                      (`work.truncation` is set on works that look truncated, with --mark_truncated)
+//      work/preview.html               work, nav
+//      work/chapter.html               work_title, work_author, work_language, chapter, part, part_number, nav
+//      work/series.html                series, works, parts (every part of the series, `work` is null for runs of
+//                                      parts from `first` to `last` that aren't in the ePub), nav
+//      work/end.html                   work, epub_tags_links, series_continuations (series, next_part, missing_part,
+//                                      next_available), nav
+//
+// `nav` is the navigation bar of the page (header, footer, previous, next, up, next_in_series, works_index, listing),
+//      which the built-in templates render by including work/nav_bar.html, so an overridden work page that
+//      includes it needs a Tera version of work/nav_bar.html in --templates as well (the same goes for
+//      work/series_heading.html and work/series_parts.html, which render `series_info` and `parts`)
+//
+// A few differences from askama to keep in mind:
+//      - A `Work` is either `{ "Single": <work> }` or `{ "Series": [<series>, [<work>, ...]] }`,
+//          so use `{% if work.Single %}` instead of `{% if let Work::Single(work) = work %}`
+//      - Options are either their value or null, so `series.total_parts` instead of `series.total_parts.unwrap()`
+//      - Method calls like `.len()` are filters in Tera, like `| length`
+//      - Reading times (`work.reading_time`, `chapter.reading_time`) are already formatted, and word counts of
+//          chapters are `chapter.word_count`
+//      - Categories are their lowercase names ("fandoms", "relationships", ...), and their translated names are
+//          in `locale.category_names`, like `locale.category_names[category]`
+
+// An askama template that can also be replaced by a Tera template from --templates
+pub trait OverridableTemplate: askama::Template + Serialize {
+    // Path of the built-in template, relative to the templates directory
+    const TEMPLATE_PATH: &'static str;
+}
+
+// Tera hides the useful part of its errors (which line, what went wrong) in the error's source chain
+fn error_message (err: &tera::Error) -> String {
+    let mut message = err.to_string();
+    let mut source = std::error::Error::source(err);
+    while let Some(cause) = source {
+        message.push_str(&format!(": {cause}"));
+        source = cause.source();
+    }
+    message
+}
+
+pub struct TemplateOverrides {
+    tera: Tera,
+    overridden: HashSet<String>,
+}
+
+impl TemplateOverrides {
+
+    // No overrides at all, every page uses its built-in template
+    pub fn none () -> Self {
+        TemplateOverrides {
+            tera: Tera::default(),
+            overridden: HashSet::new(),
+        }
+    }
+
+    // Load every file inside of `templates_dir` (recursively) as a Tera template named by its path relative to `templates_dir`
+    pub fn load (templates_dir: &Path) -> Result<Self, String> {
+        let mut files: Vec<(PathBuf, Option<String>)> = Vec::new();
+        let mut dirs = vec![ templates_dir.to_path_buf() ];
+        while let Some(dir) = dirs.pop() {
+            let entries = fs::read_dir(&dir).map_err(| err | {
+                format!("Error reading templates directory {}: {err}", dir.display())
+            })?;
+
+            for entry in entries {
+                let path = entry.map_err(| err | format!("Error reading templates directory {}: {err}", dir.display()))?.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                }
+                else {
+                    // Template names always use '/', so they match TEMPLATE_PATH no matter the platform
+                    let name = path.strip_prefix(templates_dir).unwrap()
+                        .components()
+                        .map(| component | component.as_os_str().to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join("/");
+                    files.push((path, Some(name)));
+                }
+            }
+        }
+
+        let mut tera = Tera::default();
+        // Escape everything the same way askama escapes the built-in templates
+        tera.autoescape_on(vec![ ".html", ".xhtml", ".xml", ".svg" ]);
+        tera.add_template_files(files.clone()).map_err(| err | {
+            format!("Error loading templates from {}: {}", templates_dir.display(), error_message(&err))
+        })?;
+
+        Ok(TemplateOverrides {
+            tera,
+            overridden: files.into_iter().filter_map(| (_, name) | name).collect(),
+        })
+    }
+
+    // Render `template` with its override, if there is one, or with its built-in template otherwise
+    pub fn render <T: OverridableTemplate> (&self, template: &T) -> Result<String, String> {
+        if !self.overridden.contains(T::TEMPLATE_PATH) {
+            return template.render().map_err(| err | err.to_string());
+        }
+
+        let context = Context::from_serialize(template).map_err(| err | error_message(&err))?;
+        self.tera.render(T::TEMPLATE_PATH, &context).map_err(| err | error_message(&err))
+    }
+}
@@ -1,32 +1,84 @@
-use std::{collections::HashMap, fs, path::Path};
-use crate::{epub::file_templating::{category_index::{CategoryIndex, CategoryListing}, category_listing_index::CategoryListingIndex, content_opf::ContentOpf, index_index::IndexIndex, toc::TableOfContents, work::{chapter::WorkChapter, introduction::WorkIntroduction, preview::WorkPreview, series::SeriesTemplate}, works_index::WorksIndex}, html::types::{Anchor, Category, Work, WorkSeries, WorkStruct}};
+use std::{collections::HashMap, fs, path::{Path, PathBuf}};
+use crate::{theme::Theme, epub::{book::Book, locale::Locale, recommendations::RecommendationWeights, template_overrides::{OverridableTemplate, TemplateOverrides}, file_templating::{category_index::{CategoryIndex, CategoryListing, IndexOrder}, pagination::{Pagination, page_file_name, paginate}, category_listing_index::{CategoryListingIndex, co_occurrences}, content_opf::{ContentOpf, ManifestItem}, cover::{CoverImage, CoverPage, cover_media_type}, index_index::IndexIndex, split::VolumesIndex, stats::LibraryStats, toc::{TableOfContents, TocOptions, group_works}, work::{chapter::WorkChapter, end::WorkEnd, introduction::{WorkIntroduction, association_links}, navigation::{NavBarPosition, NavBars, ReadingOrder}, preview::WorkPreview, series::{SeriesDirectory, SeriesTemplate}}, works_index::WorksIndex}}, html::{process_html::{ao3_link_key, ao3_work_id}, sanitize_html::unescape_html, split_html::split_html, types::{Anchor, Category, Relationship, RelationshipKind, Work, WorkSeries, WorkStruct}}};
 
-pub struct EpubWriter {
+// Settings shared by every ePub written in one run of the program
+pub struct EpubWriterOptions {
+    // Chapters with more than this many bytes of content get split over several xhtmls
+    pub max_chapter_bytes: Option<usize>,
+    // User-provided cover image, used instead of a generated one
+    pub cover_image: Option<PathBuf>,
+    pub theme: Theme,
+    pub templates: TemplateOverrides,
+    // Labels used around the works (index names, "Chapters", "Summary", ...)
+    pub locale: Locale,
+    // Reading speed for the reading time estimates
+    pub words_per_minute: usize,
+    // How many similar works to recommend on each work's introduction page, and how similarity is scored
+    pub recommendations: usize,
+    pub recommendation_weights: RecommendationWeights,
+    // Order of the entries of category indexes, and how many entries (or works, for listing pages) go on each page
+    pub index_order: IndexOrder,
+    pub index_page_size: Option<usize>,
+    // Optional entries of toc.ncx
+    pub toc: TocOptions,
+    // Where the previous / next / up links go on content pages
+    pub nav_bars: NavBarPosition,
+}
+
+pub struct EpubWriter <'a> {
     // Every time we write an xhtml to the staging directory, we need to track that xhtml path
     //      because content.opf will want a full log of all xhtmls in the ePub
     // content.opf also specifies the order in which xhtmls will appear in the ePub,
     //      so the order the xhtml paths get inserted into this list is also the order of the content
     //      of this ePub
-    all_xhtmls: Vec<String>
+    all_xhtmls: Vec<String>,
+
+    // Everything else that needs to be listed in the content.opf manifest (images, for example)
+    // These don't go in the spine
+    resources: Vec<ManifestItem>,
+
+    // Path of the page showing the cover image, for the content.opf guide
+    cover_page: Option<String>,
+
+    options: &'a EpubWriterOptions,
 }
 
-impl EpubWriter {
+// Everything the pages of a work link out to, worked out once for the whole ePub before any work gets written
+struct WorkLinks <'a> {
+    category_listings: &'a HashMap<Category, HashMap<String, CategoryListing<'a>>>,
+    // "You might also like", keyed by work id
+    recommendations: HashMap<usize, Vec<Anchor>>,
+    nav_bars: NavBars<'a>,
+    series_directory: SeriesDirectory<'a>,
+    // Every work in the ePub keyed by its AO3 number, for related works that are in here too
+    works_by_ao3_id: HashMap<&'a str, &'a WorkStruct>,
+}
+
+impl <'a> EpubWriter <'a> {
+
+    pub fn new (options: &'a EpubWriterOptions) -> Self {
+        // Fonts were already copied in with the rest of the theme, they just need to be in the manifest
+        let resources = options.theme.fonts.iter().enumerate().map(| (index, font) | ManifestItem {
+            id: format!("font{index}"),
+            href: font.href(),
+            media_type: String::from(font.media_type),
+        }).collect();
 
-    pub fn new () -> Self {
         EpubWriter {
-            all_xhtmls: Vec::new()
+            all_xhtmls: Vec::new(),
+            resources,
+            cover_page: None,
+            options,
         }
     }
 
     // Takes an askama template, writes it to the desired path, and expects all errors
     // SIDE EFFECT: if the write path is an xhtml, the path will be added to `&mut all_xhtmls`
-    fn render_and_write <T: askama::Template> (&mut self, path: &Path, template: T) {
-        fs::write(&path, template
-            .render()
-            .expect(
-                &format!("Error rendering template for {}", path.to_str().unwrap())[..]
-            )
-            .to_string()
+    // Uses the user's override of the template from --templates if there is one
+    fn render_and_write <T: OverridableTemplate> (&mut self, path: &Path, template: T) {
+        fs::write(&path, self.options.templates
+            .render(&template)
+            .unwrap_or_else(| err | panic!("Error rendering template for {}: {err}", path.to_str().unwrap()))
         ).expect(
             &format!("Error writing rendered template for {}", path.to_str().unwrap())[..]
         );
@@ -38,17 +90,65 @@ impl EpubWriter {
         }
     }
 
-    fn assign_playback_to_work_struct (running_play_order: &mut usize, work: &mut WorkStruct) {
-        work.playback_id = *running_play_order;
-        *running_play_order += 1;
+    // Cover image (content/cover.<ext>) ->
+    //      Either the image the user gave us, or a generated SVG with the title and some details about the works
+    // Cover page (content/cover.xhtml) ->
+    //      Page that just shows the cover image
+    //      Needs to be written before anything else so that it is the first page in the spine
+    fn write_cover (&mut self, out_dir_path: &Path, title: &str, works: &[Work]) {
+        let locale = &self.options.locale;
+        let content_path = out_dir_path.join("content");
+
+        let (image_file_name, media_type) = match &self.options.cover_image {
+            Some(cover_image) => {
+                // Already validated when the arguments were parsed
+                let media_type = cover_media_type(cover_image).unwrap();
+                let extension = cover_image.extension().unwrap().to_str().unwrap().to_lowercase();
+                let image_file_name = format!("cover.{extension}");
+                fs::copy(cover_image, content_path.join(&image_file_name)).unwrap_or_else(| err | {
+                    panic!("Error copying cover image {}: {err}", cover_image.to_str().unwrap())
+                });
+                (image_file_name, media_type)
+            },
+            None => {
+                self.render_and_write(
+                    &content_path.join("cover.svg"), 
+                    CoverImage::new(locale, title, works)
+                );
+                (String::from("cover.svg"), "image/svg+xml")
+            },
+        };
 
+        self.resources.push(ManifestItem {
+            id: String::from("cover-image"),
+            href: format!("content/{image_file_name}"),
+            media_type: String::from(media_type),
+        });
+
+        let cover_page_path = content_path.join("cover.xhtml");
+        self.render_and_write(
+            &cover_page_path, 
+            CoverPage {
+                locale,
+                title: unescape_html(title),
+                image_href: image_file_name,
+            }
+        );
+        self.cover_page = Some(String::from(cover_page_path.to_str().unwrap()));
+    }
+
+    // Reading times depend on the locale and --wpm, so they can only be worked out here rather than at ingestion
+    fn assign_reading_times_to_work_struct (options: &EpubWriterOptions, work: &mut WorkStruct) {
         for chapter in &mut work.chapters {
-            chapter.playback_id = *running_play_order;
-            *running_play_order += 1;
+            chapter.reading_time = options.locale.reading_time(chapter.word_count, options.words_per_minute);
         }
+        work.reading_time = options.locale.reading_time(work.computed_word_count(), options.words_per_minute);
     }
 
-    fn write_work_struct (&mut self, work: &WorkStruct, out_dir_path: &Path, category_listings: &HashMap<Category, HashMap<String, CategoryListing>>, series: Option<(&WorkSeries, &Vec<WorkStruct>)>) {
+    fn write_work_struct (&mut self, work: &WorkStruct, out_dir_path: &Path, series: Option<(&WorkSeries, &Vec<WorkStruct>)>, links: &WorkLinks) {
+        let locale = &self.options.locale;
+        let WorkLinks { category_listings, recommendations, nav_bars, series_directory, works_by_ao3_id } = links;
+
         // Make the folder where all the content for this work will be storeds
         let work_content_path = out_dir_path.join("content").join(
             format!("work-{}", work.id)
@@ -61,47 +161,65 @@ impl EpubWriter {
         //      Listing of all categories and subcategories in this work
         self.render_and_write(
             &work_content_path.join(format!("work-{}.xhtml", work.id)), 
-            WorkIntroduction::new(&work, &category_listings, series)
+            WorkIntroduction::new(locale, &work, &category_listings, series_directory.series_info(work), association_links(locale, work, works_by_ao3_id), recommendations.get(&work.id).map_or(&[], | anchors | &anchors[..]), nav_bars.introduction(work, series, locale))
         );
 
         // Work preview -> Summary
         self.render_and_write(
             &work_content_path.join(format!("work-{}-preview.xhtml", work.id)), 
-            WorkPreview { work: &work }
+            WorkPreview { locale, work: &work, nav: nav_bars.preview(work, series) }
         );
 
         // Chapters -> 
         //      Actual content of the work
         for chapter in work.chapters.iter() {
-            self.render_and_write(
-                &work_content_path.join(format!("work-{}-chapter-{}.xhtml", work.id, chapter.order)), 
-                WorkChapter {  
-                    work_author: &work.author,
-                    work_title: &work.title,
-                    chapter: &chapter,
-                }
-            );
+            let parts = match self.options.max_chapter_bytes {
+                Some(max_chapter_bytes) => split_html(&chapter.data, max_chapter_bytes),
+                None => vec![ chapter.data.clone() ],
+            };
+
+            // The first part keeps the normal chapter file name, so everything that links to the chapter still works
+            // Any extra parts go in the spine right after it
+            //      work-<id>-chapter-<order>.xhtml, work-<id>-chapter-<order>-part-1.xhtml, ...
+            for (part_number, part) in parts.iter().enumerate() {
+                let file_name = match part_number {
+                    0 => format!("work-{}-chapter-{}.xhtml", work.id, chapter.order),
+                    _ => format!("work-{}-chapter-{}-part-{}.xhtml", work.id, chapter.order, part_number),
+                };
+
+                self.render_and_write(
+                    &work_content_path.join(file_name), 
+                    WorkChapter {  
+                        locale,
+                        work_author: &work.author,
+                        work_title: &work.title,
+                        work_language: &work.language,
+                        chapter: &chapter,
+                        part,
+                        part_number,
+                        nav: nav_bars.chapter(work, series, chapter.order, part_number, parts.len()),
+                    }
+                );
+            }
         }
+
+        // End of work ->
+        //      Final stats and tags, and where to go next in the series
+        self.render_and_write(
+            &work_content_path.join(format!("work-{}-end.xhtml", work.id)), 
+            WorkEnd::new(locale, work, category_listings, series_directory.series_info(work), nav_bars.end(work))
+        );
     }
 
-    pub fn write_epub_files(&mut self, out_dir_path: &Path, out_name: &str, categories: &[Category], mut works: Vec<Work>) {
-        // Assign correct playback ids to the works
-        // Used in the table of contents page
-        // Impossible to do within askama itself, so they need to be pre-computed
-        let mut running_play_order = 0;
-        for work in &mut works {
-            match work {
-                Work::Single(work_struct) => {
-                    EpubWriter::assign_playback_to_work_struct(&mut running_play_order, work_struct);
-                },
-                Work::Series(ser, works) => {
-                    ser.playback_id = running_play_order;
-                    running_play_order += 1;
+    pub fn write_epub_files(&mut self, out_dir_path: &Path, categories: &[Category], book: Book) {
+        let Book { name: out_name, metadata, mut works, volumes } = book;
+        let locale = &self.options.locale;
 
-                    works.iter_mut().for_each(| work_struct | {
-                        EpubWriter::assign_playback_to_work_struct(&mut running_play_order, work_struct);
-                    });
-                },
+        // Reading times are shown all over the place, and are impossible to work out within askama itself,
+        //      so they need to be pre-computed
+        for work in &mut works {
+            for work_struct in work.work_structs_mut() {
+                EpubWriter::assign_reading_times_to_work_struct(self.options, work_struct);
             }
         }
     
@@ -116,128 +234,196 @@ impl EpubWriter {
         let mut category_listings: HashMap<Category, HashMap<String, CategoryListing>> = HashMap::new();
     
         let indexes_path = out_dir_path.join("indexes");
+
+        self.write_cover(out_dir_path, &metadata.title, &works);
     
         // indexes/index_index.xhtml -> 
         //      Index of the categories
         self.render_and_write(
             &indexes_path.join("index_index.xhtml"), 
             IndexIndex {
-                output_name: String::from(out_name),
+                locale,
+                output_name: metadata.title.clone(),
                 categories: &categories,
+                has_volumes: volumes.is_some(),
             }
         );
+
+        // indexes/volumes_index.xhtml ->
+        //      When this book is one volume of a bigger book, the list of every volume and which works it holds
+        if let Some(book_volumes) = &volumes {
+            self.render_and_write(
+                &indexes_path.join("volumes_index.xhtml"), 
+                VolumesIndex {
+                    locale,
+                    output_name: metadata.title.clone(),
+                    current_volume: book_volumes.current_volume,
+                    volumes: &book_volumes.volumes,
+                }
+            );
+        }
+    
+        let work_structs: Vec<&WorkStruct> = works.iter()
+            .flat_map(| work | work.work_structs())
+            .collect();
     
+        for category in &*categories {
+            category_listings.insert(category.clone(), CategoryListing::accumulate(category, &work_structs));
+        }
+
         // indexes/work_index.xhtml -> 
-        //      Index of all works and all of their chapters
+        //      Index of all works and all of their chapters (after the listings, which the headings of
+        //      --toc_group_by link to)
         self.render_and_write(
             &indexes_path.join("works_index.xhtml"), 
             WorksIndex {
-                output_name: String::from(out_name),
+                locale,
+                output_name: metadata.title.clone(),
                 categories: &categories,
                 works: &works,
+                groups: group_works(locale, &works, &category_listings, &self.options.toc),
             }
         );
 
-        let mut work_structs: Vec<&WorkStruct> = Vec::new();
-        for work in &works {
-            match work {
-                Work::Single(ws) => work_structs.push(ws),
-                Work::Series(_, works) => works.iter().for_each(| ws | {
-                    work_structs.push(ws);
-                }),
+        // Relationship listings get split into romantic and platonic sub-indexes, and each relationship listing
+        //      links back to the character listings of its members
+        // Both of those need lookups that only make sense once every category has been accumulated
+        let mut relationships_by_link: HashMap<&str, &Relationship> = HashMap::new();
+        for work in &work_structs {
+            for relationship in &work.relationships {
+                relationships_by_link.insert(ao3_link_key(&relationship.anchor.link), relationship);
             }
         }
-    
-        for category in &*categories {
-            // Accumulate listing of subcategories for each category
-            // Iterate over all the works and get all subcategories inside of this category
-            //      and store them as a CategoryListing
-            // When more than one work shares a category/sub-category, then add that work to the list
-            //      of works in that CategoryListing
-            // Accumulate all information in this hashmap
-            // Key is the link to the subcategory (subcategories are always of `Anchor` struct type, so they all have a link and a name)
-            // Value is the accumulated list of all works under the category/subcategory combination
-            let mut listings: HashMap<String, CategoryListing> = HashMap::new();
-
-            for work in &work_structs {
-    
-                // Extract subcategory list from this work for this category
-                let work_category_entries = match category {
-                    // Titles need to created on the spot using the work data itself
-                    Category::Titles => &vec![Anchor { link: work.link.clone(), name: work.title.clone() }],
-                    // Since there is only one author, just clone the author element and store inside of a vec
-                    // (Because the rest of the function expects a vec)
-                    // (Need to clone the author element because the expected type is Vec<Anchor>, not Vec<&Anchor>)
-                    Category::Authors => &vec![ work.author.clone() ],
-
-                    _ => work.category_data.get(&category).unwrap(),
-                };
-    
-                // Check every subcategory in this work/category combination
-                for work_category_entry in work_category_entries {
-                    // If this subcategory was found already, add the work the accumulating list 
-                    if let Some(existing_listing) = listings.get_mut(&work_category_entry.link) {
-                        existing_listing.count += 1;
-                        existing_listing.works.push(&work);
-                    }
-                    // Otherwise create a new CategoryListing object for the subcategory
-                    else {
-                        listings.insert(work_category_entry.link.clone(), CategoryListing { 
-                            id: listings.len(), 
-                            name: work_category_entry.name.clone(), 
-                            count: 1,
-                            works: vec![ &work ]
-                        });
-                    }
-                }
-            }
-    
-            // Once all subcategory listings have been accumulated in the hashmap, translate
-            //      the hashmap into a list of just (references to) the values in the map
-            let mut listing_info: Vec<&CategoryListing<'_>> = listings
+
+        // Characters are matched to relationship members by name, since that is all a relationship tag gives us
+        let characters_by_name: HashMap<&String, &CategoryListing> = match category_listings.get(&Category::Characters) {
+            Some(listings) => listings.values().map(| listing | (&listing.name, listing)).collect(),
+            None => HashMap::new(),
+        };
+
+        for category in categories {
+            let listings = category_listings.get(category).unwrap();
+            let category_path = indexes_path.join(category.to_string());
+
+            // Translate the hashmap of accumulated listings into a list of just (references to) the values in the map
+            // CategoryIndex::pages takes care of sorting them
+            let listing_info: Vec<&CategoryListing<'_>> = listings
                 .values()
                 .collect();
-    
-            // Then sort all the subcategories by how many works were in that subcategory, descending
-            listing_info.sort_by(| a, b | b.count.cmp(&a.count));
+
+            // Relationships also get a sub-index for each kind of relationship
+            // Relationship sub-index (indexes/relationships/relationships-<kind>-index.xhtml) ->
+            //      Same as the category index, but only with the relationships of that kind
+            // Example: Relationships > Romantic: Steve Rogers/Bucky Barnes (20), Peter Parker/MJ (3)
+            // They're written right after the category index that links to them, so they follow it in the spine
+            let mut subindexes: Vec<Anchor> = Vec::new();
+            let mut subindex_pages: Vec<(String, CategoryIndex)> = Vec::new();
+            if *category == Category::Relationships {
+                for kind in [ RelationshipKind::Romantic, RelationshipKind::Platonic ] {
+                    let kind_listing_info: Vec<&CategoryListing<'_>> = listing_info.iter()
+                        .filter(| listing | relationships_by_link.get(ao3_link_key(&listing.link)).map(| rel | rel.kind) == Some(kind))
+                        .copied()
+                        .collect();
+
+                    let subindex_file_stem = format!("{category}-{kind}-index");
+                    let kind_name = match kind {
+                        RelationshipKind::Romantic => locale.romantic,
+                        RelationshipKind::Platonic => locale.platonic,
+                    };
+                    subindexes.push(Anchor { 
+                        link: format!("../../indexes/{category}/{}", page_file_name(&subindex_file_stem, 0)), 
+                        name: format!("{kind_name} ({})", kind_listing_info.len()),
+                    });
+
+                    subindex_pages.extend(CategoryIndex::pages(locale, category, Some(String::from(kind_name)), Vec::new(), kind_listing_info, self.options.index_order, self.options.index_page_size, &subindex_file_stem));
+                }
+            }
     
             // Write the category index
-            // Category index (indexes/<category>/index.xhtml) ->
+            // Category index (indexes/<category>/index.xhtml, then index-page-2.xhtml, ... if it has more than --index_page_size entries) ->
             //      List of all items in that category and the number of works with that item
-            //      Ordered by the number of works in the category item, descending
+            //      Ordered by the number of works in the category item, descending, or alphabetically with --index_order
             // Example: Fandoms: Overwatch (100), Supernatural (50), Pokemon (2)
-            self.render_and_write(
-                &indexes_path.join(format!("{}/index.xhtml", category)), 
-                CategoryIndex {
-                    category: category.to_string(),
-                    categories: listing_info
-                }
-            );
+            let pages = CategoryIndex::pages(locale, category, None, subindexes, listing_info, self.options.index_order, self.options.index_page_size, "index");
+            for (file_name, page) in pages.into_iter().chain(subindex_pages) {
+                self.render_and_write(&category_path.join(file_name), page);
+            }
     
             // Because each title is (most likely) unique, there does not need to be a listing page for that category
             // The title index page will link directly to each work individually
             if *category != Category::Titles {
-                for (_, subcategory_listing) in &listings {
+                for subcategory_listing in listings.values() {
+
+                    // For relationships, link to the listing of every member character that has one
+                    let members: Vec<Anchor> = match relationships_by_link.get(ao3_link_key(&subcategory_listing.link)) {
+                        Some(relationship) if *category == Category::Relationships => {
+                            relationship.members.iter().filter_map(| member | {
+                                characters_by_name.get(member).map(| character_listing | Anchor {
+                                    link: format!("../../indexes/{}/{}-{}-listing.xhtml", Category::Characters, Category::Characters, character_listing.id),
+                                    name: character_listing.name.clone(),
+                                })
+                            }).collect()
+                        },
+                        _ => Vec::new(),
+                    };
     
                     // Write the category listing index
-                    // Category listing index (indexes/<category>/<category>-<subcategory_id>-listing.xhtml) ->
+                    // Category listing index (indexes/<category>/<category>-<subcategory_id>-listing.xhtml, split into
+                    //      -page-2, -page-3, ... when it has more than --index_page_size works) ->
                     //      List of all works inside of a subcategory inside of a category
-                    //      No particular order
+                    //      In the same order as the works index
                     // Example: Fandoms -> Pokemon: "Gotta Catch 'Em All", "Who's that Pokemon?"
-                    self.render_and_write(
-                        &indexes_path.join(format!("{category}/{category}-{}-listing.xhtml", subcategory_listing.id)), 
-                        CategoryListingIndex {
-                            category: category.to_string(),
-                            listing_name: &subcategory_listing.name,
-                            listing: &subcategory_listing.works
-                        }
-                    );
+                    let file_stem = format!("{category}-{}-listing", subcategory_listing.id);
+                    let pages = paginate(&subcategory_listing.works, self.options.index_page_size);
+                    let total_pages = pages.len();
+                    let mut members = Some(members);
+                    for (page_index, page) in pages.into_iter().enumerate() {
+                        let first_page = page_index == 0;
+                        self.render_and_write(
+                            &category_path.join(page_file_name(&file_stem, page_index)), 
+                            CategoryListingIndex {
+                                locale,
+                                category: category.to_string(),
+                                listing_name: &subcategory_listing.name,
+                                listing: page,
+                                members: members.take().unwrap_or_default(),
+                                co_occurrences: if first_page { co_occurrences(subcategory_listing, &category_listings) } else { Vec::new() },
+                                pagination: Pagination::new(&file_stem, page_index, total_pages),
+                            }
+                        );
+                    }
                 }
             }
-    
-            category_listings.insert(category.clone(), listings);
         }
     
+        // indexes/stats.xhtml ->
+        //      Totals and breakdowns of everything in this ePub
+        self.render_and_write(
+            &indexes_path.join("stats.xhtml"), 
+            LibraryStats::new(locale, self.options.words_per_minute, metadata.title.clone(), &work_structs, &category_listings)
+        );
+    
+        // Every content page in the order it's read, for the navigation bars and the playOrder of toc.ncx
+        let reading_order = ReadingOrder::new(locale, &works);
+
+        let links = WorkLinks {
+            category_listings: &category_listings,
+            // Similar works for the "You might also like" section of each introduction page
+            recommendations: self.options.recommendation_weights.recommend(&work_structs, self.options.recommendations),
+            // Previous / next / up links of every content page, in the same order the pages get written below
+            nav_bars: NavBars {
+                position: self.options.nav_bars,
+                reading_order: &reading_order,
+                category_listings: &category_listings,
+                listing_category: self.options.toc.group_by.clone().unwrap_or(Category::Fandoms),
+            },
+            // Every series a work is part of, including the ones it isn't grouped under
+            series_directory: SeriesDirectory::new(&works),
+            works_by_ao3_id: work_structs.iter()
+                .filter_map(| work | Some((ao3_work_id(&work.link)?, *work)))
+                .collect(),
+        };
+    
         for work in &works {
             match work {
                 Work::Series(work_series, work_structs) => {
@@ -246,18 +432,21 @@ impl EpubWriter {
                     self.render_and_write(
                         &out_dir_path.join("content").join("series").join(format!("series-{}.xhtml", work_series.id)), 
                         SeriesTemplate {
+                            locale,
                             series: work_series,
-                            works: work_structs
+                            works: work_structs,
+                            parts: links.series_directory.parts(&work_series.link),
+                            nav: links.nav_bars.series(work_series),
                         }
                     );
 
                     // Then all the works write after it
                     for work_struct in work_structs {
-                        self.write_work_struct(work_struct, out_dir_path, &category_listings, Some((work_series, work_structs)));
+                        self.write_work_struct(work_struct, out_dir_path, Some((work_series, work_structs)), &links);
                     }
                 },
                 // For single works, just write the work normally
-                Work::Single(work_struct) => self.write_work_struct(work_struct, out_dir_path, &category_listings, None),
+                Work::Single(work_struct) => self.write_work_struct(work_struct, out_dir_path, None, &links),
             }
             
         }
@@ -265,16 +454,20 @@ impl EpubWriter {
         // toc.ncx
         self.render_and_write(
             &out_dir_path.join("toc.ncx"), 
-            TableOfContents {
-                output_name: String::from(out_name),
-                categories: &categories,
-                works: &works
-            }
+            TableOfContents::new(locale, metadata.title.clone(), categories, &works, &reading_order, &category_listings, volumes.is_some(), &self.options.toc)
         );
     
+        // dc:language -> The language of the works if they all share one, otherwise the language of the labels
+        let mut work_languages = works.iter().flat_map(| work | work.work_structs()).map(| work_struct | work_struct.language.code);
+        let first_language = work_languages.next();
+        let language = match first_language {
+            Some(code) if code != "und" && work_languages.all(| other | other == code) => String::from(code),
+            _ => String::from(locale.code),
+        };
+
         self.render_and_write(
             &out_dir_path.join("content.opf"),
-            ContentOpf::new(String::from(out_name), &self.all_xhtmls)
+            ContentOpf::new(out_name, &metadata, &language, locale, &self.all_xhtmls, &self.resources, self.cover_page.as_ref())
         );
     
     }
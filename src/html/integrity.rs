@@ -0,0 +1,79 @@
+use std::fmt;
+
+use serde::Serialize;
+use crate::html::{sanitize_html::unescape_html, types::{Work, WorkStruct}};
+
+// What AO3 says a work should have against what was actually found in the downloaded file
+// A work only gets one of these when it looks like the download was cut short (see `check_download`)
+#[derive(Debug, Clone, Serialize)]
+pub struct TruncationReport {
+    pub expected_words: usize,
+    pub found_words: usize,
+    // None when AO3 didn't list a chapter count
+    pub expected_chapters: Option<usize>,
+    pub found_chapters: usize,
+}
+
+fn plural (count: usize, word: &str) -> String {
+    match count {
+        1 => format!("{count} {word}"),
+        _ => format!("{count} {word}s"),
+    }
+}
+
+impl fmt::Display for TruncationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AO3 lists {}", plural(self.expected_words, "word"))?;
+        if let Some(expected_chapters) = self.expected_chapters {
+            write!(f, " and {}", plural(expected_chapters, "chapter"))?;
+        }
+        write!(f, ", but the download has {} and {}", plural(self.found_words, "word"), plural(self.found_chapters, "chapter"))
+    }
+}
+
+// Compare the word count and chapter count AO3 lists for `work` with what was parsed out of the file
+// Browsers and download managers sometimes save a page before it has finished loading, which leaves a file that
+//      looks fine but is missing the end of the work
+// Word counts never match exactly (AO3 counts a little differently), so they only count as a mismatch when they're
+//      more than `tolerance_percent` apart
+// Chapter counts have to match exactly
+pub fn check_download (work: &WorkStruct, tolerance_percent: f64) -> Option<TruncationReport> {
+    let expected_words = work.word_count();
+    let found_words = work.computed_word_count();
+    let found_chapters = work.chapters.len();
+
+    // AO3 didn't give a word count (or it didn't parse), so there's nothing to compare against
+    let words_mismatch = expected_words > 0
+        && (expected_words.abs_diff(found_words) as f64) > expected_words as f64 * tolerance_percent / 100.0;
+    let chapters_mismatch = work.chapters_posted.is_some_and(| posted | posted != found_chapters);
+
+    if !words_mismatch && !chapters_mismatch {
+        return None;
+    }
+
+    Some(TruncationReport {
+        expected_words,
+        found_words,
+        expected_chapters: work.chapters_posted,
+        found_chapters,
+    })
+}
+
+// Check every work and print a warning for each one that looks truncated
+// With `mark_works`, the report is also kept on the work so it shows up on the work's introduction page
+// Returns how many works looked truncated
+pub fn check_downloads (works: &mut [Work], tolerance_percent: f64, mark_works: bool) -> usize {
+    let mut truncated = 0;
+    for work in works {
+        for work_struct in work.work_structs_mut() {
+            if let Some(report) = check_download(work_struct, tolerance_percent) {
+                println!("WARNING: '{}' by {} looks truncated or partially saved: {report}.", unescape_html(&work_struct.title), unescape_html(&work_struct.author.name));
+                truncated += 1;
+                if mark_works {
+                    work_struct.truncation = Some(report);
+                }
+            }
+        }
+    }
+    truncated
+}
@@ -1,3 +1,9 @@
+pub(crate) mod integrity;
+pub(crate) mod process_epub;
 pub(crate) mod process_html;
+pub(crate) mod process_series;
+pub(crate) mod process_web_page;
 pub(crate) mod sanitize_html;
+pub(crate) mod split_html;
+pub(crate) mod titles;
 pub(crate) mod types;
\ No newline at end of file
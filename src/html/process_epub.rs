@@ -0,0 +1,260 @@
+use lazy_static::lazy_static;
+use std::{fs::read_dir, io::Error, path::Path, process::Command};
+use scraper::{ElementRef, Html, Selector};
+use crate::html::{process_html::{process_associations, process_author, process_multi_chapter, process_single_chapter, process_work_meta}, sanitize_html::sanitize_html, types::*};
+
+// AO3's own ePub downloads, as a second way in next to the HTML files of `process_ao3_htmls`
+// AO3 makes its ePubs by running the HTML download through calibre, which splits it into one file for the preface,
+//      one per chapter and one for the afterword, and renames most of the classes ("meta group" -> "calibre1") while
+//      keeping the ones AO3 styles (message, tags, heading, userstuff)
+// So putting the files of the spine back together gives (close to) the HTML download the ePub was made from
+//
+// The ePubs are read with the `unzip` command, the same way they are written with `zip` (see create_zip.rs)
+
+// `unzip` takes the names of the files to extract as wildcard patterns, so the wildcard characters AO3 can put in file
+//      names (they're made from the work's title) need escaping
+fn unzip_pattern (entry: &str) -> String {
+    entry.chars().map(| ch | match ch {
+        '[' | ']' | '*' | '?' | '\\' => format!("\\{ch}"),
+        _ => String::from(ch),
+    }).collect()
+}
+
+fn read_epub_entry (epub_path: &Path, entry: &str) -> Option<String> {
+    let output = Command::new("unzip")
+        .arg("-p")
+        .arg(epub_path)
+        .arg(unzip_pattern(entry))
+        .output();
+    match output {
+        Ok(output) if output.status.success() => String::from_utf8(output.stdout).ok(),
+        Ok(_) => None,
+        Err(err) => {
+            eprintln!("Could not run unzip on {} (is it installed?): {err}", epub_path.as_os_str().display());
+            None
+        },
+    }
+}
+
+// Links inside of the ePub are relative to the file they're in and URL encoded ("Chapter%201.xhtml")
+fn decode_href (href: &str) -> String {
+    let bytes = href.as_bytes();
+    let mut decoded: Vec<u8> = Vec::new();
+    let mut index = 0;
+    while index < bytes.len() {
+        // A "%" that isn't followed by two hex digits is just a "%"
+        let hex = href.get(index + 1..index + 3).filter(| hex | bytes[index] == b'%' && hex.bytes().all(| byte | byte.is_ascii_hexdigit()));
+        match hex.and_then(| hex | u8::from_str_radix(hex, 16).ok()) {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            },
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            },
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// Paths (inside of the ePub) of every file of the spine, in reading order
+// META-INF/container.xml says where the package file is, and the package file lists the files (manifest) and the
+//      order they're read in (spine)
+// Neither are HTML, but the HTML parser still finds their elements and attributes, which is all that's needed here
+fn spine_paths (epub_path: &Path) -> Option<Vec<String>> {
+    lazy_static! {
+        static ref rootfile_selector:  Selector = Selector::parse("rootfile").unwrap();
+        static ref item_selector:      Selector = Selector::parse("item").unwrap();
+        static ref itemref_selector:   Selector = Selector::parse("itemref").unwrap();
+    }
+
+    let container = Html::parse_document(&read_epub_entry(epub_path, "META-INF/container.xml")?);
+    let package_path = String::from(container.select(&rootfile_selector).next()?.attr("full-path")?);
+    let package = Html::parse_document(&read_epub_entry(epub_path, &package_path)?);
+
+    // Files in the manifest are relative to the package file
+    let package_dir = match package_path.rfind('/') {
+        Some(slash) => &package_path[..=slash],
+        None => "",
+    };
+    let manifest: Vec<(&str, String)> = package.select(&item_selector)
+        .filter_map(| item | Some((item.attr("id")?, format!("{package_dir}{}", decode_href(item.attr("href")?)))))
+        .collect();
+
+    Some(package.select(&itemref_selector)
+        .filter_map(| itemref | itemref.attr("idref"))
+        .filter_map(| idref | manifest.iter().find(| (id, _) | *id == idref).map(| (_, path) | path.clone()))
+        .collect())
+}
+
+// The contents of every file of the ePub one after the other, as one HTML document
+fn stitch_epub (epub_path: &Path) -> Option<Html> {
+    lazy_static! {
+        static ref body_selector: Selector = Selector::parse("body").unwrap();
+    }
+
+    let bodies: Vec<String> = spine_paths(epub_path)?.iter()
+        .filter_map(| path | read_epub_entry(epub_path, path))
+        .filter_map(| xhtml | Html::parse_document(&xhtml).select(&body_selector).next().map(| body | body.inner_html()))
+        .collect();
+    Some(Html::parse_document(&format!("<html><body>{}</body></html>", bodies.join("\n"))))
+}
+
+// The summary is the blockquote right after the "Summary" label of the preface, so that a work without a summary
+//      doesn't end up with its notes as its summary
+fn preface_summary (doc: &Html) -> String {
+    lazy_static! {
+        static ref label_selector: Selector = Selector::parse("#preface p").unwrap();
+    }
+
+    doc.select(&label_selector)
+        .find(| label | label.text().collect::<String>().trim() == "Summary")
+        .and_then(| label | label.next_siblings().find_map(ElementRef::wrap))
+        .filter(| blockquote | blockquote.value().name() == "blockquote")
+        .map_or(String::new(), | blockquote | String::from(blockquote.inner_html().trim()))
+}
+
+// Chapters of the stitched ePub
+// Every chapter file has its own copy of the `#chapters` wrapper, with the chapter's heading right inside of it for
+//      works with a single chapter, or inside of a (renamed) "meta group" div for the others
+// Headings in the chapter text itself (inside of the userstuff divs) aren't chapters
+fn stitched_chapters (doc: &Html) -> Vec<Chapter> {
+    lazy_static! {
+        static ref single_chapter_header_selector:  Selector = Selector::parse("#chapters > h2").unwrap();
+        static ref multi_chapter_header_selector:   Selector = Selector::parse("#chapters > div > h2.heading").unwrap();
+    }
+
+    if let Some(single_chapter_header) = doc.select(&single_chapter_header_selector).next() {
+        return vec![ process_single_chapter(single_chapter_header) ];
+    }
+
+    doc.select(&multi_chapter_header_selector)
+        .filter_map(| header | header.parent().and_then(ElementRef::wrap))
+        .filter(| meta_group | !meta_group.value().classes().any(| class | class.starts_with("userstuff")))
+        .enumerate()
+        .map(| (index, meta_group) | process_multi_chapter(index, meta_group))
+        .collect()
+}
+
+// Read a work out of an AO3 ePub download
+// Returns None for ePubs that don't look like they came from AO3
+pub fn process_ao3_epub (epub_path: &Path, id: usize) -> Option<WorkStruct> {
+    lazy_static! {
+        static ref title_selector:          Selector = Selector::parse("p.message b").unwrap();
+        static ref link_selector:           Selector = Selector::parse("p.message a:nth-of-type(2)").unwrap();
+        static ref tag_container_selector:  Selector = Selector::parse("dl.tags").unwrap();
+        static ref author_elt_selector:     Selector = Selector::parse("#preface a[rel=\"author\"]").unwrap();
+    }
+
+    let doc = stitch_epub(epub_path)?;
+    let title = doc.select(&title_selector).next()?.inner_html();
+    let link = doc.select(&link_selector).next()?.inner_html();
+    let meta = process_work_meta(doc.select(&tag_container_selector).next()?);
+
+    let author = process_author(doc.select(&author_elt_selector).next());
+    let (gifts, associations) = process_associations(&doc);
+
+    Some(WorkStruct {
+        id,
+        title,
+        link,
+        category_data: meta.category_data,
+        relationships: meta.relationships,
+        series: meta.series,
+        wc: meta.wc.unwrap_or(String::from("Unknown")),
+        chapters_posted: meta.chapters_posted,
+        language: meta.language.unwrap_or(Language::from_ao3_name("")),
+        summary: sanitize_html(preface_summary(&doc)),
+        author,
+        gifts,
+        associations,
+        chapters: stitched_chapters(&doc),
+        reading_time: String::new(),
+        truncation: None,
+    })
+}
+
+// Every AO3 ePub in `root`, with ids starting at `first_id` (so they don't clash with the works from HTML files)
+pub fn process_ao3_epubs (root: &str, first_id: usize) -> Result<Vec<WorkStruct>, Error> {
+    let path = Path::new(root);
+    let mut epub_paths: Vec<_> = read_dir(path)?
+        .filter_map(| entry | entry.ok())
+        .map(| entry | entry.path())
+        .filter(| entry_path | entry_path.is_file() && entry_path.extension().is_some_and(| extension | extension.eq_ignore_ascii_case("epub")))
+        .collect();
+    epub_paths.sort();
+
+    Ok(epub_paths.iter().enumerate().filter_map(| (index, epub_path) | {
+        let work_struct = process_ao3_epub(epub_path, first_id + index);
+        if work_struct.is_none() {
+            eprintln!("{} doesn't look like an AO3 ePub (skipping)", epub_path.as_os_str().display());
+        }
+        work_struct
+    }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs::read_to_string};
+    use super::*;
+    use crate::html::process_html::process_html;
+
+    fn fixture (path: &str) -> String {
+        format!("{}/tests/fixtures/{path}", env!("CARGO_MANIFEST_DIR"))
+    }
+
+    #[test]
+    fn hrefs_are_decoded () {
+        assert_eq!(decode_href("Snow%20Days%20%5Bx%5D_split_000.xhtml"), "Snow Days [x]_split_000.xhtml");
+        assert_eq!(decode_href("caf%C3%A9.xhtml"), "café.xhtml");
+        assert_eq!(decode_href("plain.xhtml"), "plain.xhtml");
+        // Anything that isn't a "%" with two hex digits after it is left alone
+        assert_eq!(decode_href("100%"), "100%");
+        assert_eq!(decode_href("100%2"), "100%2");
+        assert_eq!(decode_href("%zz%+1%%41"), "%zz%+1%A");
+    }
+
+    #[test]
+    fn unzip_wildcards_are_escaped () {
+        assert_eq!(unzip_pattern("OEBPS/Snow Days [x]_split_000.xhtml"), "OEBPS/Snow Days \\[x\\]_split_000.xhtml");
+        assert_eq!(unzip_pattern("What? * \\ Why"), "What\\? \\* \\\\ Why");
+        assert_eq!(unzip_pattern("OEBPS/content.opf"), "OEBPS/content.opf");
+    }
+
+    // AO3 makes its ePubs from its HTML downloads, so both should read as the same work
+    #[test]
+    fn epub_matches_html_download () {
+        let from_epub = process_ao3_epub(Path::new(&fixture("epub/download.epub")), 0).unwrap();
+        let from_html = process_html(Html::parse_document(&read_to_string(fixture("epub/download.html")).unwrap()), 0);
+
+        assert_eq!(from_epub.title, from_html.title);
+        assert_eq!(from_epub.link, from_html.link);
+        assert_eq!((&from_epub.author.name, &from_epub.author.link), (&from_html.author.name, &from_html.author.link));
+        assert_eq!(from_epub.summary, from_html.summary);
+        assert_eq!(from_epub.wc, from_html.wc);
+        assert_eq!(from_epub.chapters_posted, from_html.chapters_posted);
+        assert_eq!(from_epub.language.code, from_html.language.code);
+        assert_eq!(from_epub.series, from_html.series);
+
+        let tags = | work: &WorkStruct | -> HashMap<Category, Vec<(String, String)>> {
+            work.category_data.iter()
+                .map(| (category, anchors) | (category.clone(), anchors.iter().map(| anchor | (anchor.name.clone(), anchor.link.clone())).collect()))
+                .collect()
+        };
+        assert_eq!(tags(&from_epub), tags(&from_html));
+
+        let chapters = | work: &WorkStruct | -> Vec<(usize, String, String, String, usize)> {
+            work.chapters.iter()
+                .map(| chapter | (chapter.order, chapter.title.clone(), chapter.summary.clone(), chapter.data.clone(), chapter.word_count))
+                .collect()
+        };
+        assert_eq!(chapters(&from_epub).len(), 2);
+        assert_eq!(chapters(&from_epub), chapters(&from_html));
+    }
+
+    #[test]
+    fn unreadable_epubs_are_skipped () {
+        assert!(process_ao3_epub(Path::new(&fixture("epub/missing.epub")), 0).is_none());
+    }
+}
@@ -2,7 +2,7 @@ use lazy_static::lazy_static;
 use std::{collections::HashMap, fs::{read_dir, read_to_string}, io::Error, path::Path};
 use regex::Regex;
 use scraper::{Html, Selector, ElementRef};
-use crate::html::{sanitize_html::sanitize_html, types::*};
+use crate::html::{process_web_page::{is_work_page, process_work_page}, process_series::{apply_series_totals, attach_series_details, is_series_page, process_series_page}, sanitize_html::sanitize_html, titles::sort_key, types::*};
 
 fn element_ref_next_element_sibling <'a> (elt: ElementRef<'a>) -> Option<ElementRef<'a>> {
     elt.next_siblings().find(| sibling | {
@@ -15,12 +15,12 @@ fn element_ref_next_element_sibling <'a> (elt: ElementRef<'a>) -> Option<Element
     })
 }
 
-fn process_single_chapter (header_elt: ElementRef<'_>) -> Chapter {
+pub(crate) fn process_single_chapter (header_elt: ElementRef<'_>) -> Chapter {
     let title = header_elt.inner_html();
     return finish_chapter(0, title, None, header_elt);
 }
 
-fn process_multi_chapter (order: usize, meta_group_elt: ElementRef<'_>) -> Chapter {
+pub(crate) fn process_multi_chapter (order: usize, meta_group_elt: ElementRef<'_>) -> Chapter {
     lazy_static! {
         static ref header_selector: Selector = Selector::parse("h2.heading").unwrap();
         static ref paragraph_selector: Selector  = Selector::parse("p").unwrap();
@@ -44,16 +44,50 @@ fn process_multi_chapter (order: usize, meta_group_elt: ElementRef<'_>) -> Chapt
 }
 
 
+// Count words the same way AO3 does (close enough, anyway): anything separated by whitespace that has a letter or a
+//      number in it is a word, except for Chinese / Japanese / Korean text where every character counts as a word
+fn count_words <'a> (text: impl Iterator<Item = &'a str>) -> usize {
+    let is_cjk = | ch: char | matches!(ch as u32,
+        0x3040..=0x30FF |   // Hiragana, Katakana
+        0x3400..=0x4DBF |   // CJK Extension A
+        0x4E00..=0x9FFF |   // CJK Unified Ideographs
+        0xAC00..=0xD7AF |   // Hangul Syllables
+        0xF900..=0xFAFF     // CJK Compatibility Ideographs
+    );
+
+    text.flat_map(| text | text.split_whitespace()).map(| word | {
+        let cjk_chars = word.chars().filter(| ch | is_cjk(*ch)).count();
+        let has_other_word = word.chars().any(| ch | ch.is_alphanumeric() && !is_cjk(ch));
+        cjk_chars + has_other_word as usize
+    }).sum()
+}
+
 fn finish_chapter (order: usize, title: String, summary: Option<String>, elt: ElementRef<'_>) -> Chapter {
     let userstuff = element_ref_next_element_sibling(elt).unwrap();
+    chapter_from_userstuff(order, title, summary, userstuff)
+}
+
+// The chapter whose text is `userstuff`
+// Saved web pages start the text with a "Chapter Text" heading for screen readers, which isn't part of the chapter
+pub(crate) fn chapter_from_userstuff (order: usize, title: String, summary: Option<String>, userstuff: ElementRef<'_>) -> Chapter {
+    lazy_static! {
+        static ref landmark_selector: Selector = Selector::parse("h3.landmark").unwrap();
+    }
 
-    let data = userstuff.inner_html();
+    // The same headings are taken out of both the text and the word count
+    let mut data = userstuff.inner_html();
+    let mut landmark_words = 0;
+    for landmark in userstuff.select(&landmark_selector) {
+        data = data.replacen(&landmark.html(), "", 1);
+        landmark_words += count_words(landmark.text());
+    }
     return Chapter {
-        playback_id: 0,
         order: order,
         title: String::from(title.trim()),
-        summary: sanitize_html(summary.unwrap_or(String::from("No Summary"))),
+        summary: sanitize_html(summary.unwrap_or_default()),
         data: sanitize_html(String::from(data.trim())),
+        word_count: count_words(userstuff.text()) - landmark_words,
+        reading_time: String::new(),
     }
 }
 
@@ -78,30 +112,193 @@ fn process_anchors <'a> (dt: ElementRef<'a>, anchors: &mut Vec<Anchor>) {
 }
 
 
-fn process_html (doc: Html, id: usize) -> WorkStruct {
+// Every row of the "Series:" entry, which looks like "Part 2 of <a>Series A</a>, Part 1 of 3 of <a>Series B</a>"
+// The part number of each series is in the text right before its anchor, along with the total number of parts
+//      when AO3 gives it
+// Saved web pages wrap each row in a `span.position`, between links to the previous / next work of the series
+fn process_series (dt: ElementRef<'_>) -> Vec<Series> {
+    lazy_static! {
+        static ref part_regex: Regex = Regex::new(r"Part (?<part>\d+)(?: of (?<total>\d+))?").unwrap();
+        static ref position_selector: Selector = Selector::parse("span.position").unwrap();
+    }
+
+    let dd = element_ref_next_element_sibling(dt).unwrap();
+    let rows: Vec<ElementRef> = match dd.select(&position_selector).collect::<Vec<ElementRef>>() {
+        positions if positions.is_empty() => vec![ dd ],
+        positions => positions,
+    };
+
+    let mut series: Vec<Series> = Vec::new();
+    let mut preceding_text = String::new();
+    for node in rows.iter().flat_map(| row | row.children()) {
+        if let Some(text) = node.value().as_text() {
+            preceding_text.push_str(text);
+            continue;
+        }
+
+        let Some(anchor_elt) = ElementRef::wrap(node).filter(| elt | elt.value().name() == "a") else {
+            continue;
+        };
+        let Some(captures) = part_regex.captures_iter(&preceding_text).last() else {
+            continue;
+        };
+
+        series.push(Series {
+            name: String::from(anchor_elt.inner_html().trim()),
+            link: String::from(anchor_elt.attr("href").unwrap().trim()),
+            part_number: captures["part"].parse().unwrap(),
+            total_parts: captures.name("total").map(| total | total.as_str().parse().unwrap()),
+        });
+        preceding_text.clear();
+    }
+    series
+}
+
+// Break a relationship tag up into its member characters
+// Anchor names come from `inner_html`, so the ampersand of a platonic relationship shows up as "&amp;"
+// Members are kept in that same escaped form so that they can be compared against Characters anchor names
+fn process_relationship (anchor: &Anchor) -> Relationship {
+    let (kind, members): (RelationshipKind, Vec<&str>) = if anchor.name.contains('/') {
+        (RelationshipKind::Romantic, anchor.name.split('/').collect())
+    }
+    else {
+        (RelationshipKind::Platonic, anchor.name.split("&amp;").collect())
+    };
+
+    Relationship {
+        anchor: anchor.clone(),
+        kind,
+        members: members.into_iter()
+            .map(| member | String::from(member.trim()))
+            .filter(| member | !member.is_empty())
+            .collect(),
+    }
+}
+
+
+// AO3's number for the work at `link` ("http://archiveofourown.org/works/123/chapters/456" -> "123")
+// Works link to each other with http:// or https://, and sometimes to a chapter, so works are matched by number
+pub fn ao3_work_id (link: &str) -> Option<&str> {
+    lazy_static! {
+        static ref work_id_regex: Regex = Regex::new(r"/works/(?<id>\d+)").unwrap();
+    }
+    work_id_regex.captures(link).and_then(| captures | captures.name("id")).map(| id | id.as_str())
+}
+
+// Where `link` points on AO3 ("http://archiveofourown.org/tags/Fluff" -> "/tags/Fluff"), or `link` itself for links
+//      that go somewhere else
+// Downloads link to AO3 with http:// and pages saved from the site with https://, so AO3 links are matched by this
+//      rather than in full
+pub fn ao3_link_key (link: &str) -> &str {
+    lazy_static! {
+        static ref site_regex: Regex = Regex::new(r"^(?:https?:)?//(?:www\.)?archiveofourown\.org").unwrap();
+    }
+    let link = link.trim();
+    match site_regex.find(link) {
+        Some(site) => &link[site.end()..],
+        None => link,
+    }
+}
+
+// Gift recipients and related works, from the list AO3 puts above the notes (and in the afterword) of a download:
+//      "For <a>recipient</a>.", "Inspired by <a>work</a> by <a>creator</a>.", "A translation of <a>work</a> by ...",
+//      "Translation into Español available: <a>work</a> by ..."
+// Works that were inspired by this one are listed in the afterword with just "<a>work</a> by <a>creator</a>"
+// Saved web pages have the same lists in the preface and afterword of `#workskin`
+// Lists inside of the end notes themselves are the author's, not AO3's, so they're left alone
+pub(crate) fn process_associations (doc: &Html) -> (Vec<Author>, Vec<Association>) {
+    lazy_static! {
+        static ref preface_item_selector:    Selector = Selector::parse("#preface ul.associations > li, #workskin > div.preface ul.associations > li").unwrap();
+        static ref afterword_item_selector:  Selector = Selector::parse("#afterword ul > li, #children ul > li").unwrap();
+        static ref anchor_selector:          Selector = Selector::parse("a").unwrap();
+    }
+
+    let mut gifts: Vec<Author> = Vec::new();
+    let mut associations: Vec<Association> = Vec::new();
+
+    let in_userstuff = | item: &ElementRef | item.ancestors()
+        .filter_map(ElementRef::wrap)
+        .any(| ancestor | ancestor.value().classes().any(| class | class == "userstuff"));
+    let items = doc.select(&preface_item_selector).map(| item | (item, false))
+        .chain(doc.select(&afterword_item_selector).map(| item | (item, true)))
+        .filter(| (item, _) | !in_userstuff(item));
+    for (item, in_afterword) in items {
+        let anchors: Vec<Anchor> = item.select(&anchor_selector).filter_map(| anchor_elt | Some(Anchor {
+            link: String::from(anchor_elt.attr("href")?.trim()),
+            name: String::from(anchor_elt.inner_html().trim()),
+        })).collect();
+        let text = item.text().collect::<String>();
+        let text = text.trim();
+
+        let kind = if text.starts_with("For ") {
+            // Recipients without an AO3 account are only named
+            match anchors.is_empty() {
+                true => gifts.push(Anchor {
+                    link: String::new(),
+                    name: String::from(text.trim_start_matches("For ").trim_end_matches('.').trim()),
+                }),
+                false => gifts.extend(anchors),
+            }
+            continue;
+        }
+        else if text.starts_with("Inspired by") {
+            AssociationKind::InspiredBy
+        }
+        else if text.starts_with("A translation of") {
+            AssociationKind::TranslationOf
+        }
+        else if text.starts_with("A podfic of") || text.starts_with("Podfic of") {
+            AssociationKind::PodficOf
+        }
+        else if text.starts_with("Translation into") {
+            AssociationKind::Translation
+        }
+        else if in_afterword {
+            AssociationKind::Inspired
+        }
+        else {
+            continue;
+        };
+
+        // Restricted and deleted works show up without a link
+        let mut anchors = anchors.into_iter();
+        let Some(work) = anchors.next() else {
+            continue;
+        };
+        associations.push(Association {
+            kind,
+            work,
+            creators: anchors.collect(),
+        });
+    }
+    (gifts, associations)
+}
+
+// Everything in the "Rating: ... Stats:" list at the top of a work, which is laid out the same way (give or take some
+//      nesting) in downloads (`dl.tags`) and saved web pages (`dl.work.meta`)
+pub(crate) struct WorkMeta {
+    pub category_data: HashMap<Category, Vec<Anchor>>,
+    pub relationships: Vec<Relationship>,
+    pub series: Vec<Series>,
+    pub wc: Option<String>,
+    pub chapters_posted: Option<usize>,
+    pub language: Option<Language>,
+}
+
+pub(crate) fn process_work_meta (tag_container: ElementRef<'_>) -> WorkMeta {
     lazy_static! {
-        static ref title_selector:                   Selector = Selector::parse("p.message b").unwrap();
-        static ref link_selector:                    Selector = Selector::parse("p.message a:nth-of-type(2)").unwrap();
-        static ref tag_container_selector:           Selector = Selector::parse("dl.tags").unwrap();
-        static ref summary_selector:                 Selector = Selector::parse("div.meta blockquote.userstuff").unwrap();
-        static ref author_elt_selector:              Selector = Selector::parse("a[rel=\"author\"").unwrap();
-        static ref single_chapter_header_selector:   Selector = Selector::parse("#chapters > h2").unwrap();
-        static ref multi_chapters_headers_selector:  Selector = Selector::parse("#chapters > div.meta.group").unwrap();
-        
         static ref categories_regex:       Regex = Regex::new("(Category|Categories):").unwrap();
         static ref ratings_regex:          Regex = Regex::new("Ratings?:").unwrap();
         static ref fandoms_regex:          Regex = Regex::new("Fandoms?:").unwrap();
         static ref relationships_regex:    Regex = Regex::new("Relationships?:").unwrap();
         static ref characters_regex:       Regex = Regex::new("Characters?:").unwrap();
         static ref additional_tags_regex:  Regex = Regex::new("Additional Tags?:").unwrap();
+        static ref collections_regex:      Regex = Regex::new("Collections?:").unwrap();
 
-        static ref part_regex:             Regex = Regex::new(r"Part (?<part>\d+) of").unwrap();
         static ref wc_regex:               Regex = Regex::new(r"Words: (?<wc>[\d,]+)").unwrap();
+        static ref chapters_regex:         Regex = Regex::new(r"Chapters: (?<posted>[\d,]+)/").unwrap();
     }
 
-    let title = doc.select(&title_selector).next().unwrap().inner_html();
-    let link = doc.select(&link_selector).next().unwrap().inner_html();
-    
     let mut category_data: HashMap<Category, Vec<Anchor>> = HashMap::from([
         (Category::Ratings,        Vec::new()),
         (Category::Categories,     Vec::new()),
@@ -109,6 +306,7 @@ fn process_html (doc: Html, id: usize) -> WorkStruct {
         (Category::Relationships,  Vec::new()),
         (Category::Characters,     Vec::new()),
         (Category::Tags,           Vec::new()),
+        (Category::Collections,    Vec::new()),
     ]);
 
     let regexes: HashMap<Category, &Regex> = HashMap::from([
@@ -118,12 +316,14 @@ fn process_html (doc: Html, id: usize) -> WorkStruct {
         (Category::Relationships,  &*relationships_regex),
         (Category::Characters,     &*characters_regex),
         (Category::Tags,           &*additional_tags_regex),
+        (Category::Collections,    &*collections_regex),
     ]);
 
-    let mut series: Option<Series> = None;
+    let mut series: Vec<Series> = Vec::new();
     let mut wc: Option<String> = None;
+    let mut chapters_posted: Option<usize> = None;
+    let mut language: Option<Language> = None;
 
-    let tag_container = doc.select(&tag_container_selector).next().unwrap();
     for tag_container_child in tag_container.child_elements() {
         for (category, regex) in regexes.iter() {
             if regex.is_match(&tag_container_child.inner_html()[..]) {
@@ -132,40 +332,47 @@ fn process_html (doc: Html, id: usize) -> WorkStruct {
         }
 
         if tag_container_child.inner_html().trim() == "Series:" {
-            let mut tmp_vec = Vec::new();
-            process_anchors(tag_container_child, &mut tmp_vec);
-            let anchor =  tmp_vec.into_iter().next().unwrap();
-
-            let part_text = element_ref_next_element_sibling(tag_container_child).unwrap().inner_html();
-            let part_number_str = part_regex
-                .captures(&part_text)
-                .and_then(| a | a.name("part"))
-                .and_then(| mt | Some(mt.as_str()))
-                .unwrap()
-            ;
-
-            series = Some(Series {
-                name: anchor.name,
-                link: anchor.link,
-                part_number: part_number_str.parse().unwrap()
-            })
+            series = process_series(tag_container_child);
         }
 
-        if tag_container_child.inner_html() == "Stats:" {
-            let stats = element_ref_next_element_sibling(tag_container_child).unwrap().inner_html();
+        if tag_container_child.inner_html().trim() == "Language:" {
+            let language_name = element_ref_next_element_sibling(tag_container_child).unwrap().inner_html();
+            language = Some(Language::from_ao3_name(&language_name));
+        }
+
+        // Stats are one line of text in downloads and a nested list in saved web pages, so they're read as plain text
+        if tag_container_child.inner_html().trim() == "Stats:" {
+            let stats = element_ref_next_element_sibling(tag_container_child).unwrap().text()
+                .flat_map(| text | text.split_whitespace())
+                .collect::<Vec<&str>>()
+                .join(" ");
             wc = wc_regex.captures(&stats[..])
                 .and_then(| cap | cap.name("wc"))
                 .and_then(| mt | Some(String::from(mt.as_str())));
+            chapters_posted = chapters_regex.captures(&stats[..])
+                .and_then(| cap | cap.name("posted"))
+                .and_then(| mt | mt.as_str().replace(",", "").parse().ok());
         }
     }
 
-    let summary = match doc.select(&summary_selector).next() {
-        Some(summary) => String::from(summary.inner_html().trim()),
-        None => String::from("No Summary"),
-    };
+    let relationships = category_data.get(&Category::Relationships).unwrap()
+        .iter()
+        .map(process_relationship)
+        .collect();
+
+    WorkMeta {
+        category_data,
+        relationships,
+        series,
+        wc,
+        chapters_posted,
+        language,
+    }
+}
 
-    let author_elt = doc.select(&author_elt_selector).next();
-    let author = match author_elt {
+// The work's (first) author, or AO3's "Anonymous" for works in anonymous collections
+pub(crate) fn process_author (author_elt: Option<ElementRef<'_>>) -> Author {
+    match author_elt {
         Some(author_elt) => {
             Author {
                 link: String::from(author_elt.attr("href").unwrap().trim()),
@@ -178,8 +385,34 @@ fn process_html (doc: Html, id: usize) -> WorkStruct {
                 name: String::from("Anonymous")
             }
         },
+    }
+}
+
+// A work in AO3's "Download -> HTML" format (see process_web_page.rs for works saved from the site itself)
+pub(crate) fn process_html (doc: Html, id: usize) -> WorkStruct {
+    lazy_static! {
+        static ref title_selector:                   Selector = Selector::parse("p.message b").unwrap();
+        static ref link_selector:                    Selector = Selector::parse("p.message a:nth-of-type(2)").unwrap();
+        static ref tag_container_selector:           Selector = Selector::parse("dl.tags").unwrap();
+        static ref summary_selector:                 Selector = Selector::parse("div.meta blockquote.userstuff").unwrap();
+        static ref author_elt_selector:              Selector = Selector::parse("a[rel=\"author\"").unwrap();
+        static ref single_chapter_header_selector:   Selector = Selector::parse("#chapters > h2").unwrap();
+        static ref multi_chapters_headers_selector:  Selector = Selector::parse("#chapters > div.meta.group").unwrap();
+    }
+
+    let title = doc.select(&title_selector).next().unwrap().inner_html();
+    let link = doc.select(&link_selector).next().unwrap().inner_html();
+    let meta = process_work_meta(doc.select(&tag_container_selector).next().unwrap());
+
+    let summary = match doc.select(&summary_selector).next() {
+        Some(summary) => String::from(summary.inner_html().trim()),
+        None => String::new(),
     };
 
+    let author = process_author(doc.select(&author_elt_selector).next());
+
+    let (gifts, associations) = process_associations(&doc);
+
     let single_chapter_header_opt = doc.select(&single_chapter_header_selector).next();
     let multi_chapter_headers = doc.select(&multi_chapters_headers_selector);
 
@@ -196,20 +429,79 @@ fn process_html (doc: Html, id: usize) -> WorkStruct {
     
     return WorkStruct {
         id: id,
-        playback_id: 0,
         title,
         link,
-        category_data,
-        series,
-        wc: wc.unwrap_or(String::from("Unknown")),
+        category_data: meta.category_data,
+        relationships: meta.relationships,
+        series: meta.series,
+        wc: meta.wc.unwrap_or(String::from("Unknown")),
+        chapters_posted: meta.chapters_posted,
+        language: meta.language.unwrap_or(Language::from_ao3_name("")),
         summary: sanitize_html(summary),
         author,
+        gifts,
+        associations,
         chapters,
+        reading_time: String::new(),
+        truncation: None,
     };
 }
 
+// Chapters of one work saved as separate pages (or one work saved more than once) come together as a single work
+// The first file of the work keeps its details, filling in whatever it's missing from the others (AO3 only shows the
+//      summary and notes of a work on the page of its first chapter), and gets every chapter any of them had
+fn merge_saved_chapters (work_structs: Vec<WorkStruct>) -> Vec<WorkStruct> {
+    let mut merged: Vec<WorkStruct> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+
+    for work_struct in work_structs {
+        let Some(work_id) = ao3_work_id(&work_struct.link).map(String::from) else {
+            merged.push(work_struct);
+            continue;
+        };
+
+        let Some(&position) = positions.get(&work_id) else {
+            positions.insert(work_id, merged.len());
+            merged.push(work_struct);
+            continue;
+        };
+
+        let existing = &mut merged[position];
+        if existing.summary.is_empty() {
+            existing.summary = work_struct.summary;
+        }
+        if existing.gifts.is_empty() {
+            existing.gifts = work_struct.gifts;
+        }
+        if existing.associations.is_empty() {
+            existing.associations = work_struct.associations;
+        }
+        existing.chapters_posted = existing.chapters_posted.max(work_struct.chapters_posted);
+        existing.chapters.extend(work_struct.chapters);
+    }
+
+    // Chapters saved more than once are only kept once
+    for work_struct in &mut merged {
+        work_struct.chapters.sort_by_key(| chapter | chapter.order);
+        work_struct.chapters.dedup_by_key(| chapter | chapter.order);
+    }
+    merged
+}
+
+// What a saved AO3 page turned out to be
+// Pages only live until they're sorted into works and series pages, so there's nothing to gain from boxing the work
+#[allow(clippy::large_enum_variant)]
+enum Ao3Page {
+    Work(WorkStruct),
+    Series(SeriesDetails),
+}
+
+// Every work and series page among the HTML files of `root`, with work ids counting up from 0
+// Works can be AO3 HTML downloads or pages saved from the site with a browser (a whole work, or one chapter per file)
+// Saved AO3 series pages (https://archiveofourown.org/series/<id>) can go in the same directory as the works, and
+//      fill in the details of their series (see `assemble_works`)
 #[allow(unused_parens)]
-pub fn process_ao3_htmls (root: &str) -> Result<Vec<Work>, Error> {
+pub fn process_ao3_htmls (root: &str) -> Result<(Vec<WorkStruct>, Vec<SeriesDetails>), Error> {
     let path = Path::new(root);
     let entries = match read_dir(&path) {
         Ok(entries) => entries,
@@ -219,7 +511,7 @@ pub fn process_ao3_htmls (root: &str) -> Result<Vec<Work>, Error> {
         },
     };
     
-    let work_structs: Vec<WorkStruct> = entries.enumerate().filter_map(| (index, entry) | {
+    let pages: Vec<Ao3Page> = entries.enumerate().filter_map(| (index, entry) | {
         let dirent = match entry {
             Ok(dirent) => dirent,
             Err(err) => {
@@ -255,66 +547,106 @@ pub fn process_ao3_htmls (root: &str) -> Result<Vec<Work>, Error> {
         };
 
         let doc = Html::parse_document(&doc_str[..]);
-        Some(process_html(doc, index))
+        if is_work_page(&doc) {
+            return match process_work_page(&doc, &doc_str, index) {
+                Some(work_struct) => Some(Ao3Page::Work(work_struct)),
+                None => {
+                    eprintln!("Could not tell which work {} is the page of (skipping)", path.as_os_str().display());
+                    None
+                },
+            };
+        }
+        if is_series_page(&doc) {
+            return match process_series_page(&doc, &doc_str) {
+                Some(details) => Some(Ao3Page::Series(details)),
+                None => {
+                    eprintln!("Could not tell which series {} is the page of (skipping)", path.as_os_str().display());
+                    None
+                },
+            };
+        }
+        Some(Ao3Page::Work(process_html(doc, index)))
     })
     .collect();
 
-    let mut series_map: HashMap<Option<String>, Vec<WorkStruct>> = HashMap::from([
-        (None, Vec::new())
-    ]);
+    let mut work_structs: Vec<WorkStruct> = Vec::new();
+    let mut series_pages: Vec<SeriesDetails> = Vec::new();
+    for page in pages {
+        match page {
+            Ao3Page::Work(work_struct) => work_structs.push(work_struct),
+            Ao3Page::Series(details) => series_pages.push(details),
+        }
+    }
 
-    let mut series_data: HashMap<String, WorkSeries> = HashMap::new();
+    return Ok((work_structs, series_pages));
+}
 
-    work_structs.into_iter().for_each(| work_struct | {
-        match &work_struct.series {
-            Some(series) => {
-                let works = series_map.get_mut(&Some(series.link.clone()));
-                if let Some(works) = works {
-                    works.push(work_struct);
-                }
-                else {
-                    let series_link = series.link.clone();
-                    series_data.insert(series_link, WorkSeries { 
-                        id: series_data.len(),
-                        title: series.name.clone(), 
-                        link: series.link.clone(), 
-                        author: work_struct.author.clone(), 
-                        playback_id: 0
-                    });
-                    series_map.insert(Some(series.link.clone()), vec![ work_struct ]);
-                }
-            },
-            None => series_map.get_mut(&None).unwrap().push(work_struct),
-        };
-    });
+// Turn the works read from every source (HTML files, ePubs) into the final list of works
+// Chapters saved as separate pages are merged into their work, and the series pages fill in their series
+// With `keep_single_part_series`, a series that only had one of its works downloaded still gets its own series page
+//      instead of the work being listed on its own
+pub fn assemble_works (work_structs: Vec<WorkStruct>, series_pages: Vec<SeriesDetails>, keep_single_part_series: bool) -> Vec<Work> {
+    let mut work_structs = merge_saved_chapters(work_structs);
+    apply_series_totals(&mut work_structs, &series_pages);
+    let mut works = group_into_series(work_structs, keep_single_part_series);
+    attach_series_details(&mut works, series_pages);
+    works
+}
 
 
+// Put works that are part of the same series together, and sort everything by title
+// A work that is part of several series goes with the one that has the most of its works downloaded (the first one AO3
+//      lists, on a tie), and the other series still list it on their own pages
+pub fn group_into_series (work_structs: Vec<WorkStruct>, keep_single_part_series: bool) -> Vec<Work> {
+    let mut downloaded_parts: HashMap<String, usize> = HashMap::new();
+    for work_struct in &work_structs {
+        for series in &work_struct.series {
+            *downloaded_parts.entry(String::from(ao3_link_key(&series.link))).or_insert(0) += 1;
+        }
+    }
+
+    let mut series_map: HashMap<String, Vec<WorkStruct>> = HashMap::new();
+    let mut series_data: HashMap<String, WorkSeries> = HashMap::new();
     let mut works: Vec<Work> = Vec::new();
 
-    series_map.into_iter().for_each(| (series, mut work_structs) | {
-        if let Some(series_link) = series {
-            if work_structs.len() == 1 {
-                work_structs.into_iter().for_each(| work | {
-                    works.push(Work::Single(work));
-                });
-            }
-            else {
-                work_structs.sort_by(| a, b | 
-                    a.series.as_ref().unwrap().part_number.cmp(&b.series.as_ref().unwrap().part_number
-                ));
-
-                works.push(Work::Series (
-                    series_data.remove(&series_link).unwrap(),
-                    work_structs
-                ));
+    for work_struct in work_structs {
+        let mut best_series: Option<&Series> = None;
+        for series in &work_struct.series {
+            if best_series.is_none_or(| best | downloaded_parts[ao3_link_key(&series.link)] > downloaded_parts[ao3_link_key(&best.link)]) {
+                best_series = Some(series);
             }
         }
-        else {
-            return work_structs.into_iter().for_each(| work | {
-                works.push(Work::Single(work));
-            });
+
+        match best_series {
+            Some(series) if keep_single_part_series || downloaded_parts[ao3_link_key(&series.link)] > 1 => {
+                let series_link = String::from(ao3_link_key(&series.link));
+                if !series_data.contains_key(&series_link) {
+                    series_data.insert(series_link.clone(), WorkSeries {
+                        id: series_data.len(),
+                        title: series.name.clone(),
+                        link: series.link.clone(),
+                        author: work_struct.author.clone(),
+                        total_parts: None,
+                        details: None,
+                    });
+                }
+                series_map.entry(series_link).or_default().push(work_struct);
+            },
+            _ => works.push(Work::Single(work_struct)),
         }
-    });
+    }
+
+    for (series_link, mut work_structs) in series_map {
+        work_structs.sort_by_key(| work | work.part_number_in(&series_link));
+
+        let mut work_series = series_data.remove(&series_link).unwrap();
+        work_series.total_parts = work_structs.iter()
+            .flat_map(| work | work.series.iter())
+            .filter(| series | ao3_link_key(&series.link) == series_link)
+            .filter_map(| series | series.total_parts)
+            .max();
+        works.push(Work::Series(work_series, work_structs));
+    }
 
     works.sort_by(| a, b | {
         let a_title = &match &a {
@@ -327,10 +659,10 @@ pub fn process_ao3_htmls (root: &str) -> Result<Vec<Work>, Error> {
             Work::Series(work_series, _) => &work_series.title,
         };
 
-        return a_title.cmp(b_title);
+        return sort_key(a_title).cmp(&sort_key(b_title));
     });
 
-    return Ok(works);
+    works
 }
 
 
@@ -347,4 +679,57 @@ pub fn process_ao3_html (html_path: &str) -> Result<Work, Error> {
 
     let doc = Html::parse_document(&doc_str[..]);
     Ok(Work::Single( process_html(doc, 0) ))
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::epub::file_templating::category_index::CategoryListing;
+
+    fn fixture_works (dir: &str) -> Vec<Work> {
+        let (work_structs, series_pages) = process_ao3_htmls(&format!("{}/tests/fixtures/{dir}", env!("CARGO_MANIFEST_DIR"))).unwrap();
+        assemble_works(work_structs, series_pages, false)
+    }
+
+    // Parts 1 and 3 of a series downloaded from AO3, with part 2 saved from the site in between
+    #[test]
+    fn saved_page_and_downloads_share_series_and_listings () {
+        let works = fixture_works("mixed_series");
+        assert_eq!(works.len(), 1);
+        let Work::Series(work_series, parts) = &works[0] else {
+            panic!("the three parts weren't put together into their series");
+        };
+        assert_eq!(work_series.title, "Five Series");
+        assert!(work_series.details.is_some());
+        let titles: Vec<&str> = parts.iter().map(| part | &part.title[..]).collect();
+        assert_eq!(titles, vec![ "Cold Open", "Middle Ground", "Warm Close" ]);
+        let part_numbers: Vec<usize> = parts.iter().map(| part | part.part_number_in(&work_series.link)).collect();
+        assert_eq!(part_numbers, vec![ 1, 2, 3 ]);
+
+        let work_structs: Vec<&WorkStruct> = works.iter().flat_map(| work | work.work_structs()).collect();
+        for category in [ Category::Ratings, Category::Fandoms, Category::Tags, Category::Authors ] {
+            let listings = CategoryListing::accumulate(&category, &work_structs);
+            let counts: Vec<usize> = listings.values().map(| listing | listing.count).collect();
+            assert_eq!(counts, vec![ 3 ], "{category} listings");
+        }
+    }
+
+    // The chapters of a work saved one page at a time end up in one work, in order
+    #[test]
+    fn saved_chapter_pages_are_merged () {
+        let works = fixture_works("saved_chapters");
+        assert_eq!(works.len(), 1);
+        let Work::Single(work) = &works[0] else {
+            panic!("a work that isn't part of a series became a series");
+        };
+        let titles: Vec<&str> = work.chapters.iter().map(| chapter | &chapter.title[..]).collect();
+        assert_eq!(titles, vec![ "Chapter 1: First", "Chapter 2: Second" ]);
+    }
+
+    #[test]
+    fn link_keys () {
+        assert_eq!(ao3_link_key("http://archiveofourown.org/tags/Fluff"), "/tags/Fluff");
+        assert_eq!(ao3_link_key("https://archiveofourown.org/tags/Fluff"), "/tags/Fluff");
+        assert_eq!(ao3_link_key("https://www.archiveofourown.org/series/5"), "/series/5");
+        assert_eq!(ao3_link_key("https://example.com/tags/Fluff"), "https://example.com/tags/Fluff");
+    }
+}
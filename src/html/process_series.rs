@@ -0,0 +1,181 @@
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use regex::Regex;
+use scraper::{ElementRef, Html, Selector};
+use crate::html::{process_web_page::absolute_ao3_link, sanitize_html::{sanitize_html, unescape_html}, types::*};
+
+// AO3's number for the series at `link` ("https://archiveofourown.org/series/123" -> "123")
+// Works link to their series with http:// and series pages are saved from https://, so series are matched by number
+pub fn ao3_series_id (link: &str) -> Option<&str> {
+    lazy_static! {
+        static ref series_id_regex: Regex = Regex::new(r"/series/(?<id>\d+)").unwrap();
+    }
+    series_id_regex.captures(link).and_then(| captures | captures.name("id")).map(| id | id.as_str())
+}
+
+// Saved AO3 series pages have the series' details in a `dl.series.meta` block, which work downloads never have
+pub fn is_series_page (doc: &Html) -> bool {
+    lazy_static! {
+        static ref series_meta_selector: Selector = Selector::parse("dl.series.meta").unwrap();
+    }
+    doc.select(&series_meta_selector).next().is_some()
+}
+
+// Plain text of an HTML fragment, for places that can't hold markup (like dc:description)
+// Parsing it (rather than stripping the tags) decodes every kind of character reference the sanitizer might leave
+pub fn html_to_text (html: &str) -> String {
+    let fragment = Html::parse_fragment(html);
+    let text = fragment.root_element().text().collect::<Vec<&str>>().join(" ");
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+// The URL the page was saved from
+// Saved pages don't keep their address anywhere standard, so this tries (in order) the canonical link, the og:url
+//      meta tag, the "saved from url" comment browsers leave at the top, and the forms and links of the page that point
+//      back at the series (subscribe, bookmark, ...)
+fn series_page_link (doc: &Html, doc_str: &str) -> Option<String> {
+    lazy_static! {
+        static ref canonical_selector:  Selector = Selector::parse("link[rel=\"canonical\"]").unwrap();
+        static ref og_url_selector:     Selector = Selector::parse("meta[property=\"og:url\"]").unwrap();
+        static ref form_selector:       Selector = Selector::parse("form[action*=\"/series/\"]").unwrap();
+        static ref anchor_selector:     Selector = Selector::parse("a[href*=\"/series/\"]").unwrap();
+        static ref saved_from_regex:    Regex = Regex::new(r"saved from url=\(\d+\)(?<url>\S+)").unwrap();
+    }
+
+    let candidates = doc.select(&canonical_selector).filter_map(| elt | elt.attr("href"))
+        .chain(doc.select(&og_url_selector).filter_map(| elt | elt.attr("content")))
+        .chain(saved_from_regex.captures(doc_str).and_then(| captures | captures.name("url")).map(| url | url.as_str()))
+        .chain(doc.select(&form_selector).filter_map(| elt | elt.attr("action")))
+        .chain(doc.select(&anchor_selector).filter_map(| elt | elt.attr("href")));
+
+    for candidate in candidates {
+        if let Some(id) = ao3_series_id(candidate) {
+            return Some(format!("http://archiveofourown.org/series/{id}"));
+        }
+    }
+    None
+}
+
+// Anchors inside of `elt`, with links made absolute (series pages link to creators with site-relative links)
+fn absolute_anchors (elt: ElementRef<'_>) -> Vec<Anchor> {
+    lazy_static! {
+        static ref anchor_selector: Selector = Selector::parse("a").unwrap();
+    }
+
+    elt.select(&anchor_selector).filter_map(| anchor_elt | {
+        Some(Anchor {
+            link: absolute_ao3_link(anchor_elt.attr("href")?),
+            name: String::from(anchor_elt.inner_html().trim()),
+        })
+    }).collect()
+}
+
+// Every "<dt>Label:</dt><dd>value</dd>" pair directly inside of `dl`, keyed by label (without the colon)
+fn definitions <'a> (dl: ElementRef<'a>) -> HashMap<String, ElementRef<'a>> {
+    let mut definitions = HashMap::new();
+    let mut label: Option<String> = None;
+    for child in dl.child_elements() {
+        match child.value().name() {
+            "dt" => label = Some(child.text().collect::<String>().trim().trim_end_matches(':').to_string()),
+            "dd" => if let Some(label) = label.take() {
+                definitions.insert(label, child);
+            },
+            _ => {},
+        }
+    }
+    definitions
+}
+
+// Read the details of a series out of a saved AO3 series page (https://archiveofourown.org/series/<id>)
+// Returns None when the page doesn't say which series it is, since there'd be no way to match it to any works
+pub fn process_series_page (doc: &Html, doc_str: &str) -> Option<SeriesDetails> {
+    lazy_static! {
+        static ref title_selector:        Selector = Selector::parse("h2.heading").unwrap();
+        static ref series_meta_selector:  Selector = Selector::parse("dl.series.meta").unwrap();
+        static ref stats_selector:        Selector = Selector::parse("dl.stats").unwrap();
+        static ref blockquote_selector:   Selector = Selector::parse("blockquote.userstuff").unwrap();
+    }
+
+    let link = series_page_link(doc, doc_str)?;
+    let title = doc.select(&title_selector).next().map_or(String::new(), | elt | String::from(elt.inner_html().trim()));
+    let meta = definitions(doc.select(&series_meta_selector).next()?);
+
+    let text = | elt: &ElementRef | String::from(elt.text().collect::<String>().trim());
+    let userstuff = | label: &str | meta.get(label)
+        .map(| dd | dd.select(&blockquote_selector).next().map_or_else(|| dd.inner_html(), | blockquote | blockquote.inner_html()))
+        .map_or(String::new(), | html | sanitize_html(String::from(html.trim())));
+
+    let creators = meta.get("Creator").or(meta.get("Creators")).map_or(Vec::new(), | dd | absolute_anchors(*dd));
+
+    // Words, work count and completion are in a nested list of stats
+    let stats = meta.get("Stats")
+        .and_then(| dd | dd.select(&stats_selector).next())
+        .map_or(HashMap::new(), definitions);
+
+    Some(SeriesDetails {
+        link,
+        title,
+        summary: userstuff("Description"),
+        notes: userstuff("Notes"),
+        creators,
+        begun: meta.get("Series Begun").map(text),
+        updated: meta.get("Series Updated").map(text),
+        words: stats.get("Words").map(text),
+        works: stats.get("Works").and_then(| dd | text(dd).replace(",", "").parse().ok()),
+        complete: stats.get("Complete").map(| dd | text(dd).eq_ignore_ascii_case("yes")),
+    })
+}
+
+// Series pages know how many works a series has even when the works themselves don't, so fill in the total number of
+//      parts of every series entry they cover (before works are grouped into series, which works out the totals)
+pub fn apply_series_totals (work_structs: &mut [WorkStruct], series_pages: &[SeriesDetails]) {
+    let totals: HashMap<&str, usize> = series_pages.iter()
+        .filter_map(| details | Some((ao3_series_id(&details.link)?, details.works?)))
+        .collect();
+
+    for series in work_structs.iter_mut().flat_map(| work_struct | work_struct.series.iter_mut()) {
+        if let Some(total) = ao3_series_id(&series.link).and_then(| id | totals.get(id)) {
+            series.total_parts = Some(*total);
+        }
+    }
+}
+
+// Hand each series page to the series it describes
+// Only series that got grouped (see `group_into_series`) have a page in the ePub, so the details of any other series
+//      are reported and left out
+pub fn attach_series_details (works: &mut [Work], series_pages: Vec<SeriesDetails>) {
+    let mut series_by_id: HashMap<String, &mut WorkSeries> = works.iter_mut().filter_map(| work | match work {
+        Work::Series(work_series, _) => Some((String::from(ao3_series_id(&work_series.link)?), work_series)),
+        Work::Single(_) => None,
+    }).collect();
+
+    for details in series_pages {
+        match ao3_series_id(&details.link).and_then(| id | series_by_id.get_mut(id)) {
+            Some(work_series) => work_series.details = Some(details),
+            None => println!("WARNING: Saved series page of '{}' ({}) doesn't match any series in the ePub (Skipping).", unescape_html(&details.title), details.link),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::read_to_string;
+    use super::*;
+
+    #[test]
+    fn series_page () {
+        let doc_str = read_to_string(format!("{}/tests/fixtures/mixed_series/series.html", env!("CARGO_MANIFEST_DIR"))).unwrap();
+        let doc = Html::parse_document(&doc_str);
+        assert!(is_series_page(&doc));
+
+        let details = process_series_page(&doc, &doc_str).unwrap();
+        assert_eq!(details.title, "Five Series");
+        assert_eq!(ao3_series_id(&details.link), Some("5"));
+        assert_eq!(details.creators.iter().map(| creator | &creator.name[..]).collect::<Vec<&str>>(), vec![ "alice" ]);
+        assert_eq!(details.begun.as_deref(), Some("2020-01-01"));
+        assert_eq!(details.updated.as_deref(), Some("2020-03-01"));
+        assert_eq!(details.works, Some(3));
+        assert_eq!(details.complete, Some(false));
+        assert_eq!(html_to_text(&details.summary), "Three works about the seasons.");
+    }
+}
@@ -0,0 +1,226 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use scraper::{ElementRef, Html, Selector};
+use crate::html::{process_html::{ao3_work_id, chapter_from_userstuff, process_associations, process_author, process_work_meta}, sanitize_html::sanitize_html, types::*};
+
+// AO3 work pages saved from a browser ("Save page as"), either with the whole work on them
+//      (https://archiveofourown.org/works/<id>?view_full_work=true) or with one of its chapters
+//      (https://archiveofourown.org/works/<id>/chapters/<chapter id>)
+// They hold the same information as downloads, just laid out for the site: the tags are in `dl.work.meta`, the
+//      title, summary and notes are in the `div.preface.group` of `#workskin`, and every chapter is a `div.chapter`
+
+// Saved web pages of works always have the work skin wrapper and the work's meta list, which downloads never have
+pub fn is_work_page (doc: &Html) -> bool {
+    lazy_static! {
+        static ref work_page_selector: Selector = Selector::parse("#workskin, dl.work.meta").unwrap();
+    }
+    doc.select(&work_page_selector).next().is_some()
+}
+
+// Links of saved pages made absolute, the same way downloads write them (with http://, see `ao3_link_key`)
+// Pages link to a tag's works ("/tags/Fluff/works") where downloads link to the tag itself ("/tags/Fluff"), so tag
+//      links lose the "/works" to match
+pub fn absolute_ao3_link (link: &str) -> String {
+    lazy_static! {
+        static ref tag_works_regex: Regex = Regex::new(r"^(?<tag>.*/tags/[^/?#]+)/works$").unwrap();
+    }
+
+    let link = link.trim();
+    let link = match tag_works_regex.captures(link) {
+        Some(captures) => String::from(&captures["tag"]),
+        None => String::from(link),
+    };
+    match link.starts_with('/') {
+        true => format!("http://archiveofourown.org{link}"),
+        false => link,
+    }
+}
+
+// AO3's number for the work the page was saved from
+// Like series pages (see `series_page_link`), saved pages don't keep their address anywhere standard, so this tries the
+//      "saved from url" comment, the canonical link, the og:url meta tag, the kudos form, and then the links of the
+//      work's own navigation (entire work, chapter index, comments, ...)
+// Links anywhere else on the page can point to other works (related works, the previous / next work of a series), so
+//      those aren't looked at
+fn page_work_id (doc: &Html, doc_str: &str) -> Option<String> {
+    lazy_static! {
+        static ref canonical_selector:   Selector = Selector::parse("link[rel=\"canonical\"]").unwrap();
+        static ref og_url_selector:      Selector = Selector::parse("meta[property=\"og:url\"]").unwrap();
+        static ref kudos_selector:       Selector = Selector::parse("#kudo_commentable_id").unwrap();
+        static ref navigation_selector:  Selector = Selector::parse("ul.work.navigation a[href*=\"/works/\"], #chapter_index form[action*=\"/works/\"]").unwrap();
+        static ref saved_from_regex:     Regex = Regex::new(r"saved from url=\(\d+\)(?<url>\S+)").unwrap();
+    }
+
+    let links = saved_from_regex.captures(doc_str).and_then(| captures | captures.name("url")).map(| url | url.as_str())
+        .into_iter()
+        .chain(doc.select(&canonical_selector).filter_map(| elt | elt.attr("href")))
+        .chain(doc.select(&og_url_selector).filter_map(| elt | elt.attr("content")));
+    for link in links {
+        if let Some(id) = ao3_work_id(link) {
+            return Some(String::from(id));
+        }
+    }
+
+    if let Some(id) = doc.select(&kudos_selector).filter_map(| elt | elt.attr("value")).next() {
+        return Some(String::from(id.trim()));
+    }
+
+    doc.select(&navigation_selector)
+        .filter_map(| elt | elt.attr("href").or(elt.attr("action")))
+        .find_map(ao3_work_id)
+        .map(String::from)
+}
+
+// The chapter inside of a `div.chapter`, which AO3 gives the id "chapter-<position>"
+// `order` is the chapter's position (counting from 0 like downloads do) so that chapters saved one per file end up
+//      in the right order once they're merged
+fn process_chapter (index: usize, chapter_elt: ElementRef<'_>) -> Option<Chapter> {
+    lazy_static! {
+        static ref title_selector:    Selector = Selector::parse("h3.title").unwrap();
+        static ref summary_selector:  Selector = Selector::parse("div.summary blockquote.userstuff").unwrap();
+        static ref tag_regex:         Regex = Regex::new(r"<[^>]*>").unwrap();
+    }
+
+    let order = chapter_elt.value().id()
+        .and_then(| id | id.strip_prefix("chapter-"))
+        .and_then(| position | position.parse::<usize>().ok())
+        .map_or(index, | position | position.saturating_sub(1));
+
+    // The chapter's own preface (title, summary and notes) comes first, and its end notes come after the text
+    let has_class = | elt: &ElementRef, class: &str | elt.value().classes().any(| elt_class | elt_class == class);
+    let preface = chapter_elt.child_elements().find(| child | has_class(child, "preface"));
+    let userstuff = chapter_elt.child_elements().find(| child | has_class(child, "userstuff"))?;
+
+    // Titles look like "<a>Chapter 1</a>: Beginnings", and only the text is kept
+    let title = preface
+        .and_then(| preface | preface.select(&title_selector).next())
+        .map_or(String::new(), | title | tag_regex.replace_all(&title.inner_html(), "").split_whitespace().collect::<Vec<&str>>().join(" "));
+    let summary = preface
+        .and_then(| preface | preface.select(&summary_selector).next())
+        .map(| summary | String::from(summary.inner_html().trim()));
+
+    Some(chapter_from_userstuff(order, title, summary, userstuff))
+}
+
+// Every link of the work made absolute (see `absolute_ao3_link`)
+fn absolutize_links (work_struct: &mut WorkStruct) {
+    let anchors = work_struct.category_data.values_mut().flatten()
+        .chain(work_struct.relationships.iter_mut().map(| relationship | &mut relationship.anchor))
+        .chain(std::iter::once(&mut work_struct.author))
+        .chain(work_struct.gifts.iter_mut().filter(| recipient | !recipient.link.is_empty()))
+        .chain(work_struct.associations.iter_mut().flat_map(| association | std::iter::once(&mut association.work).chain(association.creators.iter_mut())));
+    for anchor in anchors {
+        anchor.link = absolute_ao3_link(&anchor.link);
+    }
+
+    for series in &mut work_struct.series {
+        series.link = absolute_ao3_link(&series.link);
+    }
+}
+
+// Read a work out of a saved AO3 work page
+// Returns None when the page doesn't say which work it is, since its chapters couldn't be matched with the ones
+//      saved in other files
+pub fn process_work_page (doc: &Html, doc_str: &str, id: usize) -> Option<WorkStruct> {
+    lazy_static! {
+        static ref meta_selector:            Selector = Selector::parse("dl.work.meta").unwrap();
+        static ref title_selector:           Selector = Selector::parse("#workskin > div.preface h2.title").unwrap();
+        static ref author_elt_selector:      Selector = Selector::parse("#workskin > div.preface .byline a[rel=\"author\"]").unwrap();
+        static ref summary_selector:         Selector = Selector::parse("#workskin > div.preface div.summary blockquote.userstuff").unwrap();
+        static ref chapter_selector:         Selector = Selector::parse("#chapters > div.chapter").unwrap();
+        static ref single_chapter_selector:  Selector = Selector::parse("#chapters > div.userstuff").unwrap();
+    }
+
+    let work_id = page_work_id(doc, doc_str)?;
+    let title = doc.select(&title_selector).next().map_or(String::new(), | title | String::from(title.inner_html().trim()));
+    let meta = process_work_meta(doc.select(&meta_selector).next()?);
+
+    let summary = match doc.select(&summary_selector).next() {
+        Some(summary) => String::from(summary.inner_html().trim()),
+        None => String::new(),
+    };
+
+    let author = process_author(doc.select(&author_elt_selector).next());
+
+    let (gifts, associations) = process_associations(doc);
+
+    // Works with a single chapter have their text right inside of #chapters, with the work's title as the chapter's
+    let mut chapters: Vec<Chapter> = doc.select(&chapter_selector).enumerate()
+        .filter_map(| (index, chapter_elt) | process_chapter(index, chapter_elt))
+        .collect();
+    if chapters.is_empty() {
+        chapters.extend(doc.select(&single_chapter_selector).next().map(| userstuff | chapter_from_userstuff(0, title.clone(), None, userstuff)));
+    }
+
+    let mut work_struct = WorkStruct {
+        id,
+        title,
+        link: format!("http://archiveofourown.org/works/{work_id}"),
+        category_data: meta.category_data,
+        relationships: meta.relationships,
+        series: meta.series,
+        wc: meta.wc.unwrap_or(String::from("Unknown")),
+        chapters_posted: meta.chapters_posted,
+        language: meta.language.unwrap_or(Language::from_ao3_name("")),
+        summary: sanitize_html(summary),
+        author,
+        gifts,
+        associations,
+        chapters,
+        reading_time: String::new(),
+        truncation: None,
+    };
+    absolutize_links(&mut work_struct);
+    Some(work_struct)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::read_to_string;
+    use super::*;
+
+    fn fixture (path: &str) -> String {
+        read_to_string(format!("{}/tests/fixtures/{path}", env!("CARGO_MANIFEST_DIR"))).unwrap()
+    }
+
+    #[test]
+    fn whole_work_page () {
+        let doc_str = fixture("mixed_series/part_2_saved.html");
+        let doc = Html::parse_document(&doc_str);
+        assert!(is_work_page(&doc));
+
+        let work = process_work_page(&doc, &doc_str, 0).unwrap();
+        assert_eq!(work.title, "Middle Ground");
+        // Links are made absolute the same way downloads write them
+        assert_eq!(work.link, "http://archiveofourown.org/works/1002");
+        assert_eq!(work.author.link, "http://archiveofourown.org/users/alice/pseuds/alice");
+        assert_eq!(work.category_entries(&Category::Tags)[0].link, "http://archiveofourown.org/tags/Fluff");
+        assert_eq!(work.series[0].link, "http://archiveofourown.org/series/5");
+        assert_eq!(work.series[0].part_number, 2);
+
+        // The "Chapter Text" headings are neither text nor words of the chapters
+        let chapters: Vec<(&str, usize)> = work.chapters.iter().map(| chapter | (&chapter.title[..], chapter.word_count)).collect();
+        assert_eq!(chapters, vec![ ("Chapter 1: Arrival", 4), ("Chapter 2", 2) ]);
+        assert!(work.chapters.iter().all(| chapter | !chapter.data.contains("Chapter Text")));
+    }
+
+    #[test]
+    fn chapter_page () {
+        let doc_str = fixture("saved_chapters/a_chapter_2.html");
+        let doc = Html::parse_document(&doc_str);
+
+        let work = process_work_page(&doc, &doc_str, 0).unwrap();
+        assert_eq!(work.link, "http://archiveofourown.org/works/1004");
+        assert_eq!(work.chapters.len(), 1);
+        // Chapter 2 of the work, whichever file it was saved in
+        assert_eq!(work.chapters[0].order, 1);
+        assert_eq!(work.chapters[0].title, "Chapter 2: Second");
+    }
+
+    #[test]
+    fn absolute_links () {
+        assert_eq!(absolute_ao3_link("/tags/Fluff/works"), "http://archiveofourown.org/tags/Fluff");
+        assert_eq!(absolute_ao3_link("/users/alice/pseuds/alice"), "http://archiveofourown.org/users/alice/pseuds/alice");
+        assert_eq!(absolute_ao3_link("https://example.com/page"), "https://example.com/page");
+    }
+}
@@ -272,3 +272,16 @@ pub fn sanitize_html (html: HTMLString) -> HTMLString {
 
     return html;
 }
+
+
+// Undo the escaping that `inner_html` does to text, for places that need the plain text back
+//      (file names, or text that askama is going to escape again anyways)
+pub fn unescape_html (html: &str) -> String {
+    html
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&nbsp;", "\u{a0}")
+        .replace("&amp;", "&")
+}
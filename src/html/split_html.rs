@@ -0,0 +1,69 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use scraper::{ElementRef, Html, Node};
+use crate::html::{sanitize_html::sanitize_html, types::HTMLString};
+
+// Scene breaks are usually an <hr/> or a paragraph of nothing but punctuation like "* * *" or "~~~"
+fn is_scene_break (elt: ElementRef<'_>) -> bool {
+    lazy_static! {
+        static ref scene_break_regex: Regex = Regex::new(r"^[\s\*~\-_=#+•·.oO0]+$").unwrap();
+    }
+
+    match elt.value().name() {
+        "hr" => true,
+        "p" | "div" | "center" => {
+            let text: String = elt.text().collect();
+            let text = text.trim();
+            // Require at least one symbol so that a paragraph of "o" or "O" doesn't count
+            !text.is_empty() && text.len() <= 20 && text.chars().any(| ch | !ch.is_alphanumeric() && !ch.is_whitespace()) && scene_break_regex.is_match(text)
+        },
+        _ => false,
+    }
+}
+
+// Split the contents of a chapter into pieces of roughly `max_bytes` each
+// Splits only ever happen between top level elements (paragraphs, for the most part) so no tag is ever cut in half
+// Once a piece is at least half full, a scene break is preferred as the place to split
+// Chapters that are already under `max_bytes` are returned untouched in a single piece
+pub fn split_html (html: &HTMLString, max_bytes: usize) -> Vec<HTMLString> {
+    if html.len() <= max_bytes {
+        return vec![ html.clone() ];
+    }
+
+    let fragment = Html::parse_fragment(html);
+
+    let mut pieces: Vec<HTMLString> = Vec::new();
+    let mut current = String::new();
+    for child in fragment.root_element().children() {
+        let (child_html, scene_break) = match child.value() {
+            Node::Element(_) => {
+                let elt = ElementRef::wrap(child).unwrap();
+                (elt.html(), is_scene_break(elt))
+            },
+            Node::Text(text) => {
+                let escaped = text.replace("&", "&amp;").replace("<", "&lt;").replace(">", "&gt;");
+                (escaped, false)
+            },
+            _ => continue,
+        };
+
+        // Scene breaks end the current piece once it is at least half full, and the break itself is dropped
+        //      since the page turn takes its place
+        if scene_break && current.len() >= max_bytes / 2 {
+            pieces.push(std::mem::take(&mut current));
+            continue;
+        }
+
+        if !current.trim().is_empty() && current.len() + child_html.len() > max_bytes {
+            pieces.push(std::mem::take(&mut current));
+        }
+        current.push_str(&child_html);
+    }
+
+    if !current.trim().is_empty() {
+        pieces.push(current);
+    }
+
+    // Re-serializing the HTML turns numeric entities back into named ones (like &nbsp;), which ePubs can't handle
+    pieces.into_iter().map(sanitize_html).collect()
+}
@@ -0,0 +1,115 @@
+use crate::html::{sanitize_html::unescape_html, types::Work};
+
+// Small words that stay lowercase in the middle of a title-cased title
+const MINOR_WORDS: [&str; 21] = [
+    "a", "an", "the",
+    "and", "but", "or", "nor", "for", "so", "yet",
+    "as", "at", "by", "in", "of", "off", "on", "per", "to", "up", "via",
+];
+
+// Leading articles that get moved out of the way when sorting ("The Winter Soldier" sorts under W)
+const ARTICLES: [&str; 3] = [ "the", "a", "an" ];
+
+// Uppercase the first letter of `word`, skipping over any leading punctuation like quotes or brackets
+fn capitalize_first_letter (word: &str) -> String {
+    let mut out = String::with_capacity(word.len());
+    let mut capitalized = false;
+    for ch in word.chars() {
+        if !capitalized && ch.is_alphabetic() {
+            out.extend(ch.to_uppercase());
+            capitalized = true;
+        }
+        else {
+            // Words starting with a digit ("5 times", "2nd") don't get capitalized
+            capitalized = capitalized || ch.is_alphanumeric();
+            out.push(ch);
+        }
+    }
+    out
+}
+
+// Words that already have a capital letter somewhere other than their first letter are acronyms ("AU", "NASA"),
+//      initialisms ("S.H.I.E.L.D.") or names with their own casing ("McCoy", "iPhone"), and are left alone
+fn has_own_casing (word: &str) -> bool {
+    word.chars().filter(| ch | ch.is_alphabetic()).skip(1).any(| ch | ch.is_uppercase())
+}
+
+// Title case `title` without ever losing the author's own capitalization:
+//      - Words are only ever capitalized, never lowercased, so proper nouns the author capitalized stay capitalized
+//      - Acronyms and words with their own mixed casing are left exactly as they are
+//      - Minor words ("of", "the", "and", ...) stay lowercase in the middle of the title, but not at the start,
+//          the end, or right after a colon / dash
+//      - Each part of a hyphenated word gets capitalized ("Spider-Man", "Self-Indulgent")
+// Titles are stored as escaped HTML, so HTML entities (like "&amp;") are skipped over untouched
+pub fn title_case (title: &str) -> String {
+    let words: Vec<&str> = title.split(' ').collect();
+    let last_word = words.iter().rposition(| word | !word.is_empty()).unwrap_or(0);
+
+    let mut starts_phrase = true;
+    let mut out: Vec<String> = Vec::with_capacity(words.len());
+    for (index, word) in words.iter().enumerate() {
+        let bare: String = word.chars().filter(| ch | ch.is_alphanumeric()).collect::<String>().to_lowercase();
+
+        let keep_as_is = word.is_empty() || word.starts_with('&') || has_own_casing(word)
+            || (!starts_phrase && index != last_word && MINOR_WORDS.contains(&&bare[..]));
+        let cased = if keep_as_is {
+            String::from(*word)
+        }
+        else {
+            word.split('-').map(capitalize_first_letter).collect::<Vec<String>>().join("-")
+        };
+        out.push(cased);
+
+        if !word.is_empty() {
+            // A period at the end of an initialism ("S.H.I.E.L.D.") doesn't end the sentence
+            starts_phrase = word.ends_with(':') || word.ends_with('?') || word.ends_with('!')
+                || (word.ends_with('.') && !has_own_casing(word))
+                || *word == "-" || *word == "&#8212;" || *word == "—";
+        }
+    }
+    out.join(" ")
+}
+
+// Title as calibre expects it in calibre:title_sort, with any leading article moved to the end
+// Example: "The Winter Soldier" -> "Winter Soldier, The"
+pub fn title_sort (title: &str) -> String {
+    let title = title.trim();
+    match title.split_once(' ') {
+        Some((first_word, rest)) if ARTICLES.contains(&&first_word.to_lowercase()[..]) && !rest.trim().is_empty() => {
+            format!("{}, {first_word}", rest.trim())
+        },
+        _ => String::from(title),
+    }
+}
+
+// Normalized key to sort titles by
+// Case insensitive, ignores leading articles and leading punctuation (quotes, brackets, ...), and compares
+//      entities by the character they stand for
+pub fn sort_key (title: &str) -> String {
+    let title = unescape_html(title).to_lowercase();
+    let title = title.trim_start_matches(| ch: char | !ch.is_alphanumeric());
+    let title = match title.split_once(' ') {
+        Some((first_word, rest)) if ARTICLES.contains(&first_word) && !rest.trim().is_empty() => rest,
+        _ => title,
+    };
+    title.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+// Title case the title of every work, chapter and series in `works`
+pub fn title_case_works (works: &mut [Work]) {
+    for work in works {
+        if let Work::Series(work_series, _) = work {
+            work_series.title = title_case(&work_series.title);
+        }
+
+        for work_struct in work.work_structs_mut() {
+            work_struct.title = title_case(&work_struct.title);
+            for chapter in &mut work_struct.chapters {
+                chapter.title = title_case(&chapter.title);
+            }
+            for series in &mut work_struct.series {
+                series.name = title_case(&series.name);
+            }
+        }
+    }
+}
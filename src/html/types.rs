@@ -1,38 +1,129 @@
 use core::fmt;
 use std::collections::HashMap;
 use derivative::Derivative;
+use serde::Serialize;
+use crate::html::{integrity::TruncationReport, process_html::ao3_link_key};
 
 pub type HTMLString = String;
 
+// Works are only ever built once and kept in a Vec, so there's nothing to gain from boxing the (much bigger) single work
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Serialize)]
 pub enum Work {
     Single(WorkStruct),
     Series(WorkSeries, Vec<WorkStruct>)
 }
 
 
+#[derive(Clone, Serialize)]
 pub struct WorkStruct {
     pub id: usize,
-    pub playback_id: usize,
     pub title: String,
     pub link: String,
     pub category_data: HashMap<Category, Vec<Anchor>>,
-    pub series: Option<Series>,
+    pub relationships: Vec<Relationship>,
+    // Every series the work is part of, in the order AO3 lists them
+    pub series: Vec<Series>,
     pub wc: String,             // string because AO3 gives us the word count with commas, and that is convenient
+    pub chapters_posted: Option<usize>,     // the "x" of "Chapters: x/y" in AO3's stats
+    pub language: Language,
     pub summary: HTMLString,
     pub author: Author,
+    // Who the work was gifted to ("For <recipient>"), recipients that aren't AO3 users have an empty link
+    pub gifts: Vec<Author>,
+    // Works this one is related to ("Inspired by", "A translation of", "Works inspired by this one", ...)
+    pub associations: Vec<Association>,
     pub chapters: Vec<Chapter>,
+    // Estimated time to read the whole work, filled in when the ePub is written (see `Chapter::reading_time`)
+    pub reading_time: String,
+    // Set when the download looks truncated and --mark_truncated is on, for the warning on the introduction page
+    pub truncation: Option<TruncationReport>,
 }
 
+impl WorkStruct {
+    // All the subcategories of this work under `category`
+    // Titles and authors aren't stored in `category_data`, so they are created on the spot from the work itself
+    pub fn category_entries (&self, category: &Category) -> Vec<Anchor> {
+        match category {
+            Category::Titles => vec![ Anchor { link: self.link.clone(), name: self.title.clone() } ],
+            Category::Authors => vec![ self.author.clone() ],
+            _ => self.category_data.get(category).cloned().unwrap_or_default(),
+        }
+    }
+
+    // AO3's word count as a number, or 0 if AO3 didn't give us one
+    pub fn word_count (&self) -> usize {
+        self.wc.replace(",", "").parse().unwrap_or(0)
+    }
+
+    // Which part of the series at `series_link` this work is, or 0 if it isn't part of that series
+    pub fn part_number_in (&self, series_link: &str) -> usize {
+        self.series.iter().find(| series | ao3_link_key(&series.link) == ao3_link_key(series_link)).map_or(0, | series | series.part_number)
+    }
+
+    // Word count of the text that was actually downloaded, as opposed to the word count AO3 reports in `wc`
+    pub fn computed_word_count (&self) -> usize {
+        self.chapters.iter().map(| chapter | chapter.word_count).sum()
+    }
+}
+
+impl WorkSeries {
+    // Creators of the series as its series page lists them, or the author of its first work without a series page
+    pub fn creators (&self) -> Vec<Author> {
+        match &self.details {
+            Some(details) if !details.creators.is_empty() => details.creators.clone(),
+            _ => vec![ self.author.clone() ],
+        }
+    }
+}
 
+impl Work {
+    // Every work struct inside of this work, in order
+    pub fn work_structs (&self) -> Vec<&WorkStruct> {
+        match self {
+            Work::Single(work_struct) => vec![ work_struct ],
+            Work::Series(_, work_structs) => work_structs.iter().collect(),
+        }
+    }
+
+    // Same as `work_structs`, but mutable
+    pub fn work_structs_mut (&mut self) -> &mut [WorkStruct] {
+        match self {
+            Work::Single(work_struct) => std::slice::from_mut(work_struct),
+            Work::Series(_, work_structs) => &mut work_structs[..],
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
 pub struct WorkSeries {
     pub id: usize,
     pub title: String,
     pub link: String,
     pub author: Author,
-    pub playback_id: usize,
+    // How many parts the series has on AO3, when any of its works (or its series page) said so
+    pub total_parts: Option<usize>,
+    // Only there when the series' own AO3 page was saved along with the works
+    pub details: Option<SeriesDetails>,
 }
 
-#[derive(Debug, Clone)]
+// What a saved AO3 series page says about the series (see `process_series_page`)
+#[derive(Clone, Serialize)]
+pub struct SeriesDetails {
+    pub link: String,
+    pub title: String,
+    // Description and notes, empty if the series has none
+    pub summary: HTMLString,
+    pub notes: HTMLString,
+    pub creators: Vec<Author>,
+    pub begun: Option<String>,
+    pub updated: Option<String>,
+    pub words: Option<String>,
+    pub works: Option<usize>,
+    pub complete: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct Anchor {
     pub name: String,
     pub link: String,
@@ -40,24 +131,138 @@ pub struct Anchor {
 
 pub type Author = Anchor;
 
-#[derive(Debug, Eq, PartialEq)]
+// Language of a work as AO3 lists it ("English", "Español", "العربية", ...), along with the BCP 47 code and
+//      text direction that go in the lang / xml:lang / dir attributes of the work's pages
+#[derive(Debug, Clone, Serialize)]
+pub struct Language {
+    pub name: String,
+    pub code: &'static str,
+    pub dir: &'static str,
+}
+
+impl Language {
+    pub fn from_ao3_name (name: &str) -> Self {
+        let name = name.trim();
+        // AO3 lists every language by its own name for itself
+        let code = match name {
+            "English" => "en",
+            "Español" => "es",
+            "Français" => "fr",
+            "Deutsch" => "de",
+            "Italiano" => "it",
+            "Português brasileiro" => "pt-BR",
+            "Português europeu" => "pt-PT",
+            "Nederlands" => "nl",
+            "Polski" => "pl",
+            "Русский" => "ru",
+            "Українська" => "uk",
+            "Čeština" => "cs",
+            "Magyar" => "hu",
+            "Română" => "ro",
+            "Ελληνικά" => "el",
+            "Türkçe" => "tr",
+            "Svenska" => "sv",
+            "Suomi" => "fi",
+            "Dansk" => "da",
+            "Norsk" => "no",
+            "Català" => "ca",
+            "Bahasa Indonesia" => "id",
+            "Tiếng Việt" => "vi",
+            "ไทย" => "th",
+            "日本語" => "ja",
+            "한국어" => "ko",
+            "中文-普通话 國語" => "zh",
+            "中文-广东话 粵語" => "yue",
+            "العربية" => "ar",
+            "עברית" => "he",
+            "فارسی" => "fa",
+            "اردو" => "ur",
+            "יידיש" => "yi",
+            _ => "und",
+        };
+
+        Language {
+            name: String::from(name),
+            code,
+            dir: match code {
+                "ar" | "he" | "fa" | "ur" | "yi" => "rtl",
+                _ => "ltr",
+            },
+        }
+    }
+}
+
+// AO3 writes romantic pairings with a slash ("A/B") and platonic ones with an ampersand ("A & B")
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RelationshipKind {
+    Romantic,
+    Platonic,
+}
+
+impl std::fmt::Display for RelationshipKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", match self {
+            RelationshipKind::Romantic => "romantic",
+            RelationshipKind::Platonic => "platonic",
+        })
+    }
+}
+
+// A relationship tag broken up into the characters that make it up
+// `anchor` is the original tag from the Relationships category, so it can still be used to look up
+//      the relationship's listing
+#[derive(Debug, Clone, Serialize)]
+pub struct Relationship {
+    pub anchor: Anchor,
+    pub kind: RelationshipKind,
+    pub members: Vec<String>,
+}
+
+// How a work relates to another work, as AO3 words it in the notes and afterword of a download
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AssociationKind {
+    // This work is inspired by / a translation of / a podfic of the other one
+    InspiredBy,
+    TranslationOf,
+    PodficOf,
+    // The other work is a translation of / inspired by this one
+    Translation,
+    Inspired,
+}
+
+// Another work this one is related to, with the link AO3 gave for it
+#[derive(Debug, Clone, Serialize)]
+pub struct Association {
+    pub kind: AssociationKind,
+    pub work: Anchor,
+    pub creators: Vec<Author>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Serialize)]
 #[allow(unused)]
 pub struct Series {
     pub name: String,
     pub link: String,
     pub part_number: usize,
+    // The "N" of "Part X of N", which AO3 doesn't always give
+    pub total_parts: Option<usize>,
 }
 
-#[derive(Derivative)]
+#[derive(Derivative, Clone, Serialize)]
 #[derivative(Debug)]
 pub struct Chapter {
     pub order: usize,
-    pub playback_id: usize,
     pub title: String,
     #[derivative(Debug(format_with = "html_formatter"))]
     pub summary: HTMLString,
     #[derivative(Debug(format_with = "html_formatter"))]
     pub data: HTMLString,
+    // Counted from the chapter text itself, since AO3 only gives a word count for the whole work
+    pub word_count: usize,
+    // Estimated time to read the chapter ("12 minutes"), filled in when the ePub is written since it depends on --wpm
+    pub reading_time: String,
 }
 
 
@@ -66,7 +271,9 @@ fn html_formatter(val: &str, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     write!(f, "{:?}", val.chars().take(10).collect::<String>())
 }
 
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+// Serialized the same way it is displayed, so it can be used as a key in template contexts
+#[derive(PartialEq, Eq, Hash, Clone, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Category {
     Titles,
     Ratings,
@@ -75,9 +282,30 @@ pub enum Category {
     Relationships,
     Characters,
     Tags,
+    Collections,
     Authors,
 }
 
+impl std::str::FromStr for Category {
+    type Err = String;
+
+    // Inverse of `Display`, so categories can be passed on the command line the same way they show up in the ePub
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.trim().to_lowercase()[..] {
+            "titles" => Ok(Category::Titles),
+            "ratings" => Ok(Category::Ratings),
+            "categories" => Ok(Category::Categories),
+            "fandoms" => Ok(Category::Fandoms),
+            "relationships" => Ok(Category::Relationships),
+            "characters" => Ok(Category::Characters),
+            "tags" => Ok(Category::Tags),
+            "collections" => Ok(Category::Collections),
+            "authors" => Ok(Category::Authors),
+            other => Err(format!("Unknown category '{other}'.  Expected one of: titles, ratings, categories, fandoms, relationships, characters, tags, collections, authors")),
+        }
+    }
+}
+
 impl std::fmt::Display for Category {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", match self {
@@ -88,6 +316,7 @@ impl std::fmt::Display for Category {
             Category::Relationships => "relationships",
             Category::Characters => "characters",
             Category::Tags => "tags",
+            Category::Collections => "collections",
             Category::Authors => "authors",
         })
     }
@@ -4,6 +4,7 @@ use std::path::Path;
 use std::process::exit;
 
 use crate::html::types::Category;
+use crate::theme::Theme;
 
 fn mimetype () -> &'static str {
     "application/epub+zip"
@@ -20,140 +21,7 @@ r#"
 "#
 }
 
-fn page_styles_css () -> &'static str {
-r#"
-@page {
-    margin-bottom: 5pt;
-    margin-top: 5pt;
-}
-"#
-}
-
-fn stylesheet_css () -> &'static str {
-r#"
-.byline {
-    display: block;
-    text-align: center;
-}
-.calibre {
-    display: block;
-    font-size: 1em;
-    padding-left: 0;
-    padding-right: 0;
-    margin: 0 5pt;
-}
-.calibre1 {
-    display: block;
-}
-.calibre2 {
-    font-weight: bold;
-}
-.calibre3 {
-    display: block;
-    font-weight: bold;
-    margin: 0;
-}
-.calibre4 {
-    display: block;
-    margin: 0 0 1em 1em;
-}
-.calibre5 {
-    display: block;
-    margin: 0 0 0 1em;
-}
-.calibre6 {
-    display: block;
-    font-size: 1.41667em;
-    font-weight: bold;
-    line-height: 1.2;
-    text-align: center;
-    margin: 0.67em 0;
-}
-.calibre7 {
-    display: block;
-    margin: 1em 0;
-}
-.calibre8 {
-    display: block;
-}
-.calibre9 {
-    font-style: italic;
-}
-.heading {
-    display: block;
-    font-size: 1.41667em;
-    font-weight: bold;
-    line-height: 1.2;
-    text-align: center;
-    margin: 0.83em 0;
-}
-.message {
-    display: block;
-    text-align: center;
-    margin: 1em 0;
-}
-.tags {
-    display: block;
-    margin: 1em 0;
-    padding: 0;
-    border: currentColor none medium;
-}
-.toc-heading {
-    display: none;
-    font-size: 1.41667em;
-    font-weight: bold;
-    line-height: 1.2;
-    margin: 0.83em 0;
-}
-.userstuff {
-    display: block;
-    font-family: serif;
-    margin: 1em;
-    padding: 0;
-}
-.userstuff1 {
-    display: block;
-    font-family: serif;
-    padding: 0;
-}
-.userstuff2 {
-    display: block;
-    font-family: serif;
-    margin: 1em 0;
-    padding: 0;
-}
-"#
-}
-
-fn toc_sheet_css () -> &'static str {
-r#"
-/* NAVIGATION */
-
-ol {
-    list-style-type: none;
-    margin: 0 0 0 2em;
-    padding: 0 0 0 0;
-}
-
-ol li {
-    margin: 0 0 0 0;
-    padding: 0 0 0 0;
-}
-
-ol li a {
-    text-decoration: none;
-    color: black;
-    background-color: red;
-    font-family: sans-serif;
-}
-
-#guide {
-    display: none;
-}
-"#
-}
-
-pub fn initialize_filesystem_for_epub (program_name: &String, out_dir_path: &Path, categories: &[Category], automatically_delete_staging_dir: bool) {
+pub fn initialize_filesystem_for_epub (program_name: &String, out_dir_path: &Path, categories: &[Category], theme: &Theme, automatically_delete_staging_dir: bool) {
 
     // First make sure that the path doesn't exist already
     // ao3_epubinator expects `out_dir_path` to be a staging directory for the program to copy files into and we don't want to collide with
@@ -234,17 +102,8 @@ pub fn initialize_filesystem_for_epub (program_name: &String, out_dir_path: &Pat
     let meta_inf_container_xml_path = meta_inf_dir.join("container.xml");
     write_file(&meta_inf_container_xml_path, container_xml());
 
-    // page_styles.cc
-    let page_style_path = out_dir_path.join("page_styles.css");
-    write_file(&page_style_path, page_styles_css());
-
-    // stylesheet.css
-    let stylesheet_path = out_dir_path.join("stylesheet.css");
-    write_file(&stylesheet_path, stylesheet_css());
-
-    // toc_sheet.css
-    let toc_sheet_path = out_dir_path.join("toc_sheet.css");
-    write_file(&toc_sheet_path, toc_sheet_css());
+    // page_styles.css, stylesheet.css, toc_sheet.css (and fonts/, if the theme has any fonts)
+    theme.write(out_dir_path);
 
     // Most importantly, mimetype
     let mimetype_path = out_dir_path.join("mimetype");
@@ -2,14 +2,21 @@ mod initialize_fs;
 mod html;
 mod epub;
 mod create_zip;
+mod theme;
 
 use std::env;
 use std::io::Write;
 use structopt::StructOpt;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::process::exit;
 
+use crate::epub::book::{Book, VolumeLimits};
+use crate::epub::file_templating::cover::cover_media_type;
+use crate::epub::template_overrides::TemplateOverrides;
+use crate::epub::{file_templating::{category_index::IndexOrder, toc::{MultiValued, TocOptions}, work::navigation::NavBarPosition}, locale::Locale, recommendations::RecommendationWeights, write_epub_files::{EpubWriter, EpubWriterOptions}};
 use crate::html::types::Category;
+use crate::theme::{BuiltinTheme, Theme};
 
 
 
@@ -18,7 +25,7 @@ use crate::html::types::Category;
 #[derive(Debug, StructOpt)]
 #[structopt(name = "AO3 Epubinator")]
 struct Opt {
-    #[structopt(short, long, help="Directory containing AO3 HTML files to ingest.")]
+    #[structopt(short, long, help="Directory containing AO3 files to ingest: HTML or ePub downloads, or work pages saved from the site with a browser (whole works, or one chapter per file, which get merged back together).  Saved AO3 series pages in it fill in the summary, notes, creators, dates and stats of their series.")]
     dir: String,
 
     #[structopt(short, long, help="File name of the output ePub.  No need to add .epub extension.  NOTE: While creating the ePub files will be stored in a staging directory with the same name as this output file name in the directory you run the program.  If a directory with this name already exists, you will be prompted to delete it.")]
@@ -28,7 +35,85 @@ struct Opt {
     keep_staging_dir: bool,
 
     #[structopt(short = "y", long = "yes", help="Flag to say yess to deleting old staging directory without being prompted")]
-    automatically_delete_staging_dir: bool
+    automatically_delete_staging_dir: bool,
+
+    #[structopt(short = "w", long = "per_work", help="Flag to write a separate ePub for every work and every series instead of one big anthology.  Each ePub is named '<output_file_name> - <title>.epub'.")]
+    per_work: bool,
+
+    #[structopt(short = "p", long = "partition_by", conflicts_with = "per-work", help="Category to split the anthology by, writing one ePub per subcategory (for example 'fandoms' or 'ratings').  Each ePub is named '<output_file_name> - <subcategory>.epub' and only indexes its own works.")]
+    partition_by: Option<Category>,
+
+    #[structopt(long = "crossovers", requires = "partition-by", help="Flag to put works with more than one subcategory of --partition_by into a single 'Crossovers' ePub.  Default is false, which puts those works into every ePub they match.")]
+    crossovers: bool,
+
+    #[structopt(long = "volume_max_mb", help="Split any ePub whose estimated (uncompressed) size is over this many megabytes into volumes named '<name> Vol. 1..N'.  Series are never split across volumes.")]
+    volume_max_mb: Option<f64>,
+
+    #[structopt(long = "volume_max_words", help="Split any ePub with more than this many words into volumes named '<name> Vol. 1..N'.  Series are never split across volumes.")]
+    volume_max_words: Option<usize>,
+
+    #[structopt(long = "volume_max_works", help="Split any ePub with more than this many works into volumes named '<name> Vol. 1..N'.  Series are never split across volumes.")]
+    volume_max_works: Option<usize>,
+
+    #[structopt(long = "max_chapter_kb", default_value = "250", help="Split chapters with more than this many kilobytes of text over several pages, at paragraph or scene break boundaries.  Some readers can't open larger pages, and others take ages to paginate them.  0 to never split chapters.")]
+    max_chapter_kb: usize,
+
+    #[structopt(short = "c", long = "cover", help="Image to use as the cover of every ePub (jpg, png, gif or svg).  By default a cover is generated with the title, the number of works, the top fandoms and the total word count (or the title, author and fandoms for a single work).")]
+    cover: Option<PathBuf>,
+
+    #[structopt(long = "theme", default_value = "default", help="Built-in look of the ePub: default, dark, large_print or dyslexia (extra letter, word and line spacing).")]
+    theme: BuiltinTheme,
+
+    #[structopt(long = "theme-dir", help="Directory of custom stylesheets and fonts applied on top of --theme.  page_styles.css, stylesheet.css or toc_sheet.css replace the built-in file of the same name, other .css files are appended to stylesheet.css, and .ttf/.otf/.woff/.woff2 fonts are embedded with the file name (minus extension) as their font-family.")]
+    theme_dir: Option<PathBuf>,
+
+    #[structopt(long = "templates", help="Directory of Tera templates that replace the built-in page templates.  A file with the same relative path as a built-in template (like 'work/introduction.html' or 'category-index.html') replaces that page, and every other page keeps its built-in template.  See src/epub/template_overrides.rs for the fields available to each page.")]
+    templates: Option<PathBuf>,
+
+    #[structopt(long = "locale", default_value = "en", help="Language of the labels around the works (index names, 'Chapters', 'Summary', ...): en, es or ar.  Works keep their own language from AO3 either way.")]
+    locale: Locale,
+
+    #[structopt(long = "wpm", default_value = "250", help="Reading speed in words per minute, for the reading time shown on chapters, in the works index and in the stats page.")]
+    wpm: usize,
+
+    #[structopt(long = "word_count_tolerance", default_value = "10", help="How far apart (in percent) the word count AO3 lists and the word count of the downloaded text can be before a work is reported as probably truncated or partially saved.  Works with fewer chapters than AO3 lists are always reported.")]
+    word_count_tolerance: f64,
+
+    #[structopt(long = "mark_truncated", help="Flag to also put a warning on the introduction page of every work that looks truncated or partially saved.  Default is false, which only reports them while building.")]
+    mark_truncated: bool,
+
+    #[structopt(long = "recommendations", default_value = "5", help="How many similar works from the same ePub to list under 'You might also like' on each work's introduction page.  0 to leave the section out.")]
+    recommendations: usize,
+
+    #[structopt(long = "recommendation_weights", default_value = "", help="How much each category counts when comparing works for recommendations, as a comma separated list of category=weight.  Defaults are fandoms=3,relationships=3,characters=2,tags=1,authors=1,categories=0.5,ratings=0.1 and any category left out keeps its default.")]
+    recommendation_weights: RecommendationWeights,
+
+    #[structopt(long = "index_order", default_value = "count", help="Order of the entries of the category indexes: count (most works first) or alphabetical (A to Z, with a jump bar to each letter).  The titles index is always alphabetical.")]
+    index_order: IndexOrder,
+
+    #[structopt(long = "index_page_size", default_value = "500", help="Split category indexes with more than this many entries, and listing pages with more than this many works, over several pages with previous / next links.  0 to never split them.")]
+    index_page_size: usize,
+
+    #[structopt(long = "ncx_listings", help="Flag to add every category listing page (every fandom, relationship, tag, ...) to the reader's table of contents, under its category.  Default is false since big anthologies have thousands of them.")]
+    ncx_listings: bool,
+
+    #[structopt(long = "ncx_series", help="Flag to add series pages to the reader's table of contents, with the works of each series under it.  Default is false, which lists the works of a series directly under the works index.")]
+    ncx_series: bool,
+
+    #[structopt(long = "toc_group_by", help="Nest the works of the works index and the reader's table of contents under a heading for each entry of this category, like fandoms, authors or ratings (any category but titles), with series nested under those.  Default is a flat list of works sorted by title.")]
+    toc_group_by: Option<Category>,
+
+    #[structopt(long = "toc_multi_valued", default_value = "each", help="With --toc_group_by, where works with more than one entry in the category go: each (under the heading of every entry) or multiple (under a single \"Multiple\" heading).")]
+    toc_multi_valued: MultiValued,
+
+    #[structopt(long = "nav_bars", default_value = "both", help="Where the navigation bars (previous / next chapter, back to the work, next work in the series, works index and the work's listing) go on series, introduction, preview and chapter pages: both, header, footer or none.")]
+    nav_bars: NavBarPosition,
+
+    #[structopt(long = "keep_single_part_series", help="Flag to give a series its own series page (with placeholders for the parts that weren't downloaded) even when only one of its works was downloaded.  Default is false, which lists such a work on its own.")]
+    keep_single_part_series: bool,
+
+    #[structopt(long = "title_case", help="Flag to title case the titles of works, series and chapters (\"the winter soldier's AU\" -> \"The Winter Soldier's AU\").  Words are only ever capitalized, so acronyms and proper nouns keep their casing.  Default is false, which keeps the author's casing.")]
+    title_case: bool,
 
 }
 
@@ -40,6 +125,64 @@ fn main() -> Result<(), std::io::Error> {
     let automatically_delete_staging_dir = opt.automatically_delete_staging_dir;
     
     let program_name = env::args().next().unwrap();
+
+    // Check the cover image before doing any work, rather than failing halfway through writing the ePub
+    if let Some(cover) = &opt.cover {
+        if !cover.is_file() {
+            eprintln!("Cover image '{}' does not exist or is not a file.", cover.display());
+            exit(1);
+        }
+        if cover_media_type(cover).is_none() {
+            eprintln!("Cover image '{}' is not a jpg, png, gif or svg.", cover.display());
+            exit(1);
+        }
+    }
+
+    // Every work has its own title, so grouping by title would just put one work under each heading
+    if opt.toc_group_by == Some(Category::Titles) {
+        eprintln!("--toc_group_by can't be titles.  Expected one of: ratings, categories, fandoms, relationships, characters, tags, authors");
+        exit(1);
+    }
+
+    // Themes and templates are also loaded up front so that any mistakes in them are reported right away
+    let theme = Theme::load(opt.theme, opt.theme_dir.as_deref()).unwrap_or_else(| err | {
+        eprintln!("{err}");
+        exit(1);
+    });
+
+    let templates = match &opt.templates {
+        Some(templates_dir) => TemplateOverrides::load(templates_dir).unwrap_or_else(| err | {
+            eprintln!("{err}");
+            exit(1);
+        }),
+        None => TemplateOverrides::none(),
+    };
+
+    let writer_options = EpubWriterOptions {
+        max_chapter_bytes: match opt.max_chapter_kb {
+            0 => None,
+            kb => Some(kb * 1024),
+        },
+        cover_image: opt.cover.clone(),
+        theme,
+        templates,
+        locale: opt.locale,
+        words_per_minute: opt.wpm,
+        recommendations: opt.recommendations,
+        recommendation_weights: opt.recommendation_weights,
+        index_order: opt.index_order,
+        index_page_size: match opt.index_page_size {
+            0 => None,
+            page_size => Some(page_size),
+        },
+        toc: TocOptions {
+            listings: opt.ncx_listings,
+            series: opt.ncx_series,
+            group_by: opt.toc_group_by,
+            multi_valued: opt.toc_multi_valued,
+        },
+        nav_bars: opt.nav_bars,
+    };
     
     let categories = [
         Category::Titles,
@@ -49,6 +192,7 @@ fn main() -> Result<(), std::io::Error> {
         Category::Relationships,
         Category::Characters,
         Category::Tags,
+        Category::Collections,
         Category::Authors
     ];
     
@@ -56,34 +200,82 @@ fn main() -> Result<(), std::io::Error> {
     // So, first parse the input as a path, take its basename, then parse again as a path
     let out_name = opt.output_file_name.replace(".epub", "");
     let out_name = String::from(Path::new(&out_name).file_name().unwrap().to_str().unwrap());
-    let out_dir_path = Path::new(&out_name);
-    
-    // The ePub has an initial directory structure that needs to initalized before we start writing
-    //      custom content (see 'copy_dir' in the root of the repo)
-    // Initialize `out_dir_path` with 'copy_dir' contents (programmatically) before continuing
-    initialize_fs::initialize_filesystem_for_epub(&program_name, out_dir_path, &categories, automatically_delete_staging_dir);
 
     // Process AO3 HTML files and store necessary data in internal structure
     print!("Ingesting AO3 HTMLs . . . ");
     std::io::stdout().flush().expect("Failed to flush stdout"); 
-    let works = html::process_html::process_ao3_htmls(&root[..]).expect("Works ingestion failed");
+    let (mut work_structs, series_pages) = html::process_html::process_ao3_htmls(&root[..]).expect("Works ingestion failed");
     println!("Done.");
 
-    // Write the ePub files to `out_dir_path`
-    print!("Writing epub files . . . ");
+    // AO3 ePub downloads in the same directory are works like any other, numbered after the HTML ones
+    print!("Ingesting AO3 ePubs . . . ");
     std::io::stdout().flush().expect("Failed to flush stdout"); 
-    let mut epub_writer = epub::write_epub_files::EpubWriter::new();
-    epub_writer.write_epub_files(out_dir_path, &out_name, &categories, works);
+    let first_epub_id = work_structs.iter().map(| work_struct | work_struct.id + 1).max().unwrap_or(0);
+    work_structs.extend(html::process_epub::process_ao3_epubs(&root[..], first_epub_id).expect("ePub ingestion failed"));
     println!("Done.");
 
-    // Zip everything together
-    create_zip::create_epub_zip_file(&out_name, out_dir_path);
-    
-    if !keep_staging_dir {
-        print!("Removing staging directory of ePub files . . . ");
+    let mut works = html::process_html::assemble_works(work_structs, series_pages, opt.keep_single_part_series);
+
+    // Catch works that were saved before the page finished loading, before they end up in an ePub unnoticed
+    let truncated = html::integrity::check_downloads(&mut works, opt.word_count_tolerance, opt.mark_truncated);
+    if truncated > 0 {
+        println!("{truncated} work(s) look truncated or partially saved.  Try downloading them from AO3 again.");
+    }
+
+    if opt.title_case {
+        html::titles::title_case_works(&mut works);
+    }
+
+    // Decide how the works are going to be split into ePubs
+    let books = if opt.per_work {
+        Book::per_work(&out_name, works)
+    }
+    else if let Some(partition_by) = &opt.partition_by {
+        Book::partition(&out_name, partition_by, opt.crossovers, works)
+    }
+    else {
+        vec![ Book::anthology(&out_name, works) ]
+    };
+
+    // Then split any of those that are too big into volumes
+    let volume_limits = VolumeLimits {
+        max_bytes: opt.volume_max_mb.map(| mb | (mb * 1024.0 * 1024.0) as usize),
+        max_words: opt.volume_max_words,
+        max_works: opt.volume_max_works,
+    };
+    let books: Vec<Book> = books.into_iter()
+        .flat_map(| book | book.split_into_volumes(&volume_limits))
+        .collect();
+    let announce_books = books.len() > 1;
+
+    for book in books {
+        let book_name = book.name.clone();
+        let out_dir_path = Path::new(&book_name);
+        if announce_books {
+            println!("Building '{book_name}.epub'");
+        }
+
+        // The ePub has an initial directory structure that needs to initalized before we start writing
+        //      custom content (see 'copy_dir' in the root of the repo)
+        // Initialize `out_dir_path` with 'copy_dir' contents (programmatically) before continuing
+        initialize_fs::initialize_filesystem_for_epub(&program_name, out_dir_path, &categories, &writer_options.theme, automatically_delete_staging_dir);
+
+        // Write the ePub files to `out_dir_path`
+        print!("Writing epub files . . . ");
         std::io::stdout().flush().expect("Failed to flush stdout"); 
-        fs::remove_dir_all(out_dir_path).expect("Deleting directory");
+        let mut epub_writer = EpubWriter::new(&writer_options);
+        epub_writer.write_epub_files(out_dir_path, &categories, book);
         println!("Done.");
+
+        // Zip everything together
+        create_zip::create_epub_zip_file(&book_name, out_dir_path);
+        
+        if !keep_staging_dir {
+            print!("Removing staging directory of ePub files . . . ");
+            std::io::stdout().flush().expect("Failed to flush stdout"); 
+            fs::remove_dir_all(out_dir_path).expect("Deleting directory");
+            println!("Done.");
+        }
     }
     
     Ok(())
@@ -0,0 +1,390 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Built-in look of the ePub
+// Every theme starts from the default Calibre-derived stylesheets below and adds its own overrides on top
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuiltinTheme {
+    Default,
+    Dark,
+    LargePrint,
+    Dyslexia,
+}
+
+impl std::str::FromStr for BuiltinTheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.trim().to_lowercase().replace(['-', ' '], "_")[..] {
+            "default" => Ok(BuiltinTheme::Default),
+            "dark" => Ok(BuiltinTheme::Dark),
+            "large_print" => Ok(BuiltinTheme::LargePrint),
+            "dyslexia" | "dyslexia_friendly" => Ok(BuiltinTheme::Dyslexia),
+            other => Err(format!("Unknown theme '{other}'.  Expected one of: default, dark, large_print, dyslexia")),
+        }
+    }
+}
+
+fn page_styles_css () -> &'static str {
+r#"
+@page {
+    margin-bottom: 5pt;
+    margin-top: 5pt;
+}
+"#
+}
+
+fn stylesheet_css () -> &'static str {
+r#"
+.byline {
+    display: block;
+    text-align: center;
+}
+.calibre {
+    display: block;
+    font-size: 1em;
+    padding-left: 0;
+    padding-right: 0;
+    margin: 0 5pt;
+}
+.calibre1 {
+    display: block;
+}
+.calibre2 {
+    font-weight: bold;
+}
+.calibre3 {
+    display: block;
+    font-weight: bold;
+    margin: 0;
+}
+.calibre4 {
+    display: block;
+    margin: 0 0 1em 1em;
+}
+.calibre5 {
+    display: block;
+    margin: 0 0 0 1em;
+}
+.calibre6 {
+    display: block;
+    font-size: 1.41667em;
+    font-weight: bold;
+    line-height: 1.2;
+    text-align: center;
+    margin: 0.67em 0;
+}
+.calibre7 {
+    display: block;
+    margin: 1em 0;
+}
+.calibre8 {
+    display: block;
+}
+.calibre9 {
+    font-style: italic;
+}
+.heading {
+    display: block;
+    font-size: 1.41667em;
+    font-weight: bold;
+    line-height: 1.2;
+    text-align: center;
+    margin: 0.83em 0;
+}
+.chapter-length {
+    display: block;
+    font-size: 0.83333em;
+    text-align: center;
+    margin: 0 0 1em 0;
+}
+.message {
+    display: block;
+    text-align: center;
+    margin: 1em 0;
+}
+.jump-bar, .pagination {
+    display: block;
+    text-align: center;
+    margin: 1em 0;
+    word-spacing: 0.4em;
+}
+.nav-bar {
+    display: block;
+    font-size: 0.83333em;
+    text-align: center;
+    margin: 1em 0;
+}
+.nav-bar p {
+    margin: 0.3em 0;
+}
+.series-part-missing {
+    color: #999;
+    font-style: italic;
+}
+.jump-bar-empty {
+    color: #999;
+}
+.index-letter {
+    display: block;
+    font-weight: bold;
+    margin: 1em 0 0.3em 0;
+}
+.truncation-warning {
+    border: 2px solid #b00;
+    padding: 0.5em;
+}
+.tags {
+    display: block;
+    margin: 1em 0;
+    padding: 0;
+    border: currentColor none medium;
+}
+.toc-heading {
+    display: none;
+    font-size: 1.41667em;
+    font-weight: bold;
+    line-height: 1.2;
+    margin: 0.83em 0;
+}
+.userstuff {
+    display: block;
+    font-family: serif;
+    margin: 1em;
+    padding: 0;
+}
+.userstuff1 {
+    display: block;
+    font-family: serif;
+    padding: 0;
+}
+.userstuff2 {
+    display: block;
+    font-family: serif;
+    margin: 1em 0;
+    padding: 0;
+}
+"#
+}
+
+fn toc_sheet_css () -> &'static str {
+r#"
+/* NAVIGATION */
+
+ol {
+    list-style-type: none;
+    margin: 0 0 0 2em;
+    padding: 0 0 0 0;
+}
+
+ol li {
+    margin: 0 0 0 0;
+    padding: 0 0 0 0;
+}
+
+ol li a {
+    text-decoration: none;
+    color: black;
+    font-family: sans-serif;
+}
+
+#guide {
+    display: none;
+}
+"#
+}
+
+fn dark_css () -> &'static str {
+r#"
+/* THEME: DARK */
+
+body, .calibre, .userstuff, .userstuff1, .userstuff2 {
+    background-color: #121212 !important;
+    color: #e0e0e0 !important;
+}
+
+a, ol li a, ol li p {
+    color: #8ab4f8 !important;
+}
+
+hr {
+    border-color: #555555;
+}
+"#
+}
+
+fn large_print_css () -> &'static str {
+r#"
+/* THEME: LARGE PRINT */
+
+body, .calibre {
+    font-size: 1.4em !important;
+    line-height: 1.6;
+}
+
+.userstuff, .userstuff1, .userstuff2 {
+    line-height: 1.6;
+}
+"#
+}
+
+fn dyslexia_css () -> &'static str {
+r#"
+/* THEME: DYSLEXIA-FRIENDLY SPACING */
+
+body, .calibre, .userstuff, .userstuff1, .userstuff2 {
+    font-family: sans-serif !important;
+    line-height: 1.8;
+    letter-spacing: 0.05em;
+    word-spacing: 0.16em;
+    text-align: left !important;
+}
+
+p {
+    margin-bottom: 1.2em;
+}
+
+i, em, .calibre9 {
+    font-style: normal;
+    font-weight: bold;
+}
+"#
+}
+
+// A font file from --theme-dir to be embedded in the ePub
+pub struct ThemeFont {
+    pub path: PathBuf,
+    pub file_name: String,
+    pub media_type: &'static str,
+}
+
+impl ThemeFont {
+    // Path of the font inside of the ePub, URL encoded so that it can be used as the manifest's href and in the
+    //      @font-face rule no matter what characters the file name has in it ("My Font.ttf" -> "fonts/My%20Font.ttf")
+    pub fn href (&self) -> String {
+        let encoded: String = self.file_name.bytes().map(| byte | match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => String::from(byte as char),
+            _ => format!("%{byte:02X}"),
+        }).collect();
+        format!("fonts/{encoded}")
+    }
+}
+
+// The stylesheets (and fonts) that end up in the ePub
+// page_styles.css, stylesheet.css and toc_sheet.css are linked from every generated page, so a theme
+//      is just a different set of contents for those three files
+pub struct Theme {
+    pub page_styles_css: String,
+    pub stylesheet_css: String,
+    pub toc_sheet_css: String,
+    pub fonts: Vec<ThemeFont>,
+}
+
+fn font_media_type (path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    match &extension[..] {
+        "ttf" => Some("application/x-font-truetype"),
+        "otf" => Some("application/vnd.ms-opentype"),
+        "woff" => Some("application/font-woff"),
+        "woff2" => Some("font/woff2"),
+        _ => None,
+    }
+}
+
+impl Theme {
+
+    // Build the theme from one of the built-in themes, then apply the contents of `theme_dir` (if any) on top of it
+    // Inside of `theme_dir`:
+    //      - page_styles.css, stylesheet.css or toc_sheet.css replace the built-in file with the same name
+    //      - Any other .css file is added to the end of stylesheet.css (so its rules win)
+    //      - .ttf, .otf, .woff and .woff2 files are embedded in fonts/, with an @font-face rule added for each
+    //          one using the file name without its extension as the font-family (so "Atkinson.ttf" can be
+    //          used as `font-family: "Atkinson"`)
+    pub fn load (builtin: BuiltinTheme, theme_dir: Option<&Path>) -> Result<Self, String> {
+        let mut stylesheet_css = String::from(stylesheet_css().trim());
+        let overrides = match builtin {
+            BuiltinTheme::Default => "",
+            BuiltinTheme::Dark => dark_css(),
+            BuiltinTheme::LargePrint => large_print_css(),
+            BuiltinTheme::Dyslexia => dyslexia_css(),
+        };
+        stylesheet_css.push_str(overrides);
+
+        let mut theme = Theme {
+            page_styles_css: String::from(page_styles_css().trim()),
+            stylesheet_css,
+            toc_sheet_css: String::from(toc_sheet_css().trim()),
+            fonts: Vec::new(),
+        };
+
+        let Some(theme_dir) = theme_dir else {
+            return Ok(theme);
+        };
+
+        let entries = fs::read_dir(theme_dir).map_err(| err | {
+            format!("Error reading theme directory {}: {err}", theme_dir.display())
+        })?;
+
+        // Sorted so that extra stylesheets are always appended in the same order
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(| entry | entry.ok().map(| entry | entry.path()))
+            .filter(| path | path.is_file())
+            .collect();
+        paths.sort();
+
+        let mut extra_css = String::new();
+        for path in paths {
+            let file_name = String::from(path.file_name().unwrap().to_str().unwrap());
+            let read_css = | path: &Path | fs::read_to_string(path).map_err(| err | {
+                format!("Error reading theme stylesheet {}: {err}", path.display())
+            });
+
+            if let Some(media_type) = font_media_type(&path) {
+                theme.fonts.push(ThemeFont { path, file_name, media_type });
+                continue;
+            }
+
+            match &file_name[..] {
+                "page_styles.css" => theme.page_styles_css = read_css(&path)?,
+                "stylesheet.css" => theme.stylesheet_css = read_css(&path)?,
+                "toc_sheet.css" => theme.toc_sheet_css = read_css(&path)?,
+                _ if file_name.to_lowercase().ends_with(".css") => {
+                    extra_css.push_str(&format!("\n\n/* {file_name} */\n"));
+                    extra_css.push_str(&read_css(&path)?);
+                },
+                _ => {},
+            }
+        }
+
+        // Font faces go first, since @font-face rules have to come before the rules that use them
+        let font_faces: String = theme.fonts.iter().map(| font | {
+            let family = Path::new(&font.file_name).file_stem().unwrap().to_str().unwrap();
+            format!("@font-face {{\n    font-family: \"{family}\";\n    src: url(\"{}\");\n}}\n", font.href())
+        }).collect();
+
+        theme.stylesheet_css = format!("{font_faces}{}{extra_css}", theme.stylesheet_css);
+        Ok(theme)
+    }
+
+    // Write the theme's stylesheets and fonts into the ePub staging directory
+    pub fn write (&self, out_dir_path: &Path) {
+        let write_file = | file: &Path, contents: &str | {
+            fs::write(file, contents).unwrap_or_else(| err | panic!("Error writing file {}: {err}", file.to_str().unwrap()))
+        };
+
+        write_file(&out_dir_path.join("page_styles.css"), &self.page_styles_css);
+        write_file(&out_dir_path.join("stylesheet.css"), &self.stylesheet_css);
+        write_file(&out_dir_path.join("toc_sheet.css"), &self.toc_sheet_css);
+
+        if !self.fonts.is_empty() {
+            let fonts_dir = out_dir_path.join("fonts");
+            fs::create_dir(&fonts_dir).unwrap_or_else(| err | panic!("Error creating {}: {err}", fonts_dir.to_str().unwrap()));
+            for font in &self.fonts {
+                let font_path = fonts_dir.join(&font.file_name);
+                fs::copy(&font.path, &font_path).unwrap_or_else(| err | {
+                    panic!("Error copying font {} to {}: {err}", font.path.to_str().unwrap(), font_path.to_str().unwrap())
+                });
+            }
+        }
+    }
+}